@@ -1,12 +1,12 @@
 use self::{
-    defs::{Pieces, Ranks, BB_SQUARES},
+    defs::{Pieces, Ranks, Squares, Wings, BB_SQUARES},
     gamestate::GameState,
     history::History,
     zobrist::ZobristKey,
     zobrist::ZobristRandoms,
 };
 use crate::{
-    defs::{Bitboard, NrOf, Piece, Side, Sides, Square, EMPTY},
+    defs::{Bitboard, Castling, NrOf, Piece, Side, Sides, Square, EMPTY},
     evaluation::{
         defs::PIECE_VALUES,
         material,
@@ -24,6 +24,9 @@ mod history;
 mod makemove;
 mod utils;
 pub mod zobrist;
+
+pub use fen::FenError;
+
 #[derive(Clone, Debug)]
 pub struct Board {
     pub bb_pieces: [[Bitboard; NrOf::PIECE_TYPES]; Sides::BOTH],
@@ -32,8 +35,15 @@ pub struct Board {
     pub gamestate: GameState,
     pub zr: Arc<ZobristRandoms>,
     pub history: History,
+    pub castling_rooks: [[Square; Wings::BOTH]; Sides::BOTH], // Rook per castling right
+    pub castling_perms: [u8; NrOf::SQUARES], // Rights kept when a piece leaves a square
+    pub chess960: bool,                      // Chess960 castling notation
 }
 
+// Rook squares of the castling rights in standard chess.
+const CASTLING_ROOKS: [[Square; Wings::BOTH]; Sides::BOTH] =
+    [[Squares::H1, Squares::A1], [Squares::H8, Squares::A8]];
+
 impl Board {
     // Create a new board with start pos or a fen string
     pub fn new() -> Self {
@@ -44,6 +54,9 @@ impl Board {
             gamestate: GameState::new(),
             zr: Arc::new(ZobristRandoms::new()),
             history: History::new(),
+            castling_rooks: CASTLING_ROOKS,
+            castling_perms: [Castling::ALL; NrOf::SQUARES],
+            chess960: false,
         }
     }
 
@@ -243,7 +256,9 @@ impl Board {
         self.bb_pieces = [[0; NrOf::PIECE_TYPES]; Sides::BOTH];
         self.bb_side = [EMPTY; Sides::BOTH];
         self.piece_list = [Pieces::NONE; NrOf::SQUARES];
-        self.gamestate = GameState::new()
+        self.gamestate = GameState::new();
+        self.castling_rooks = CASTLING_ROOKS;
+        self.castling_perms = [Castling::ALL; NrOf::SQUARES];
     }
 
     // Initialize board
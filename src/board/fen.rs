@@ -1,12 +1,14 @@
 use super::{
-    defs::{Files, Pieces, Ranks, Squares, BB_SQUARES, SQUARE_NAME},
+    defs::{Files, Pieces, Ranks, Squares, Wings, BB_RANKS, BB_SQUARES, SQUARE_NAME},
     Board,
 };
 use crate::{
-    defs::{Castling, Sides, Square, FEN_START_POSITION, MAX_GAME_MOVES, MAX_MOVE_RULE},
-    movegen::defs::{algebraic_from_str, castling_as_string},
+    defs::{
+        Castling, NrOf, Side, Sides, Square, FEN_START_POSITION, MAX_GAME_MOVES, MAX_MOVE_RULE,
+    },
+    movegen::defs::algebraic_from_str,
 };
-use std::ops::RangeInclusive;
+use std::{fmt, ops::RangeInclusive};
 
 // Define some fen things
 const LIST_OF_PIECES: &str = "kqrbnpKQRBNP";
@@ -18,9 +20,50 @@ const SPLITTER: char = '/';
 const DASH: char = '-';
 const EM_DASH: char = '–';
 const SPACE: char = ' ';
+const SIDE_NAME: [&str; Sides::BOTH] = ["White", "Black"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    PartCount(usize),        // A FEN has six parts.
+    Pieces(String),          // Piece placement that can't be read.
+    Color(String),           // Side to move that is not "w" or "b".
+    Castling(String),        // Rights that don't match the kings and rooks.
+    EnPassant(String),       // En-passant square that can't be read.
+    HalfmoveClock(String),   // Not a number, or past the move rule.
+    FullmoveNumber(String),  // Not a number, or past the game length.
+    KingCount(Side, u32),    // Each side has exactly one king.
+    PawnOnBackRank(Square),  // Pawns can't be on the first or last rank.
+    TooManyPieces(Side),     // More pieces than promotions can explain.
+    EnPassantSquare(Square), // No pawn can just have passed this square.
+    OpponentInCheck,         // The side that just moved is in check.
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::PartCount(n) => write!(f, "must have six parts, found {n}"),
+            FenError::Pieces(p) => write!(f, "invalid piece placement: {p}"),
+            FenError::Color(c) => write!(f, "invalid side to move: {c}"),
+            FenError::Castling(c) => write!(f, "invalid castling rights: {c}"),
+            FenError::EnPassant(ep) => write!(f, "invalid en-passant square: {ep}"),
+            FenError::HalfmoveClock(h) => write!(f, "invalid half-move clock: {h}"),
+            FenError::FullmoveNumber(n) => write!(f, "invalid full-move number: {n}"),
+            FenError::KingCount(side, n) => {
+                write!(f, "{} has {n} kings instead of one", SIDE_NAME[*side])
+            }
+            FenError::PawnOnBackRank(sq) => write!(f, "pawn on {}", SQUARE_NAME[*sq]),
+            FenError::TooManyPieces(side) => write!(f, "{} has too many pieces", SIDE_NAME[*side]),
+            FenError::EnPassantSquare(sq) => {
+                write!(f, "no pawn can be taken en passant on {}", SQUARE_NAME[*sq])
+            }
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
 
-type FenResult = Result<(), u8>;
+type FenResult = Result<(), FenError>;
 type FenParser = fn(board: &mut Board, part: &str) -> bool;
+type FenPart = (FenParser, fn(String) -> FenError); // Parser and its error.
 
 impl Board {
     pub fn read_fen(&mut self, fen_string: Option<&str>) -> FenResult {
@@ -35,39 +78,39 @@ impl Board {
         .collect();
 
         // Check the number of fen parts.
-        let nr_of_parts_ok = fen_parts.len() == NR_OF_FEN_PARTS;
-
-        // Set the initial result.
-        let mut result: FenResult = if nr_of_parts_ok { Ok(()) } else { Err(0) };
-        if nr_of_parts_ok {
-            let fen_parser: [FenParser; 6] = [
-                pieces,
-                color,
-                castling,
-                enpassant,
-                halfmoveclock,
-                fullmovenumber,
-            ];
-
-            let mut new_board = self.clone();
-            new_board.reset();
-
-            let mut i: usize = 0;
-            // validate all parts of the FEN string
-            while i < NR_OF_FEN_PARTS && result == Ok(()) {
-                let parser = &fen_parser[i];
-                let part = &fen_parts[i];
-                let is_ok = parser(&mut new_board, part);
-                result = if is_ok { Ok(()) } else { Err(i as u8) };
-                i += 1;
-            }
+        if fen_parts.len() != NR_OF_FEN_PARTS {
+            return Err(FenError::PartCount(fen_parts.len()));
+        }
 
-            if result == Ok(()) {
-                new_board.init();
-                *self = new_board;
+        let fen_parser: [FenPart; NR_OF_FEN_PARTS] = [
+            (pieces, FenError::Pieces),
+            (color, FenError::Color),
+            (castling, FenError::Castling),
+            (enpassant, FenError::EnPassant),
+            (halfmoveclock, FenError::HalfmoveClock),
+            (fullmovenumber, FenError::FullmoveNumber),
+        ];
+
+        let mut new_board = self.clone();
+        new_board.reset();
+
+        // Read all parts of the FEN string. The pieces are checked right
+        // away: the castling rights depend on where the kings are.
+        for (i, (parser, error)) in fen_parser.iter().enumerate() {
+            let part = &fen_parts[i];
+            if !parser(&mut new_board, part) {
+                return Err(error(part.clone()));
+            }
+            if i == 0 {
+                check_pieces(&new_board)?;
             }
         }
-        result
+
+        new_board.init();
+        check_position(&new_board)?;
+        *self = new_board;
+
+        Ok(())
     }
 
     pub fn normalize_fen(fen: &str) -> &str {
@@ -140,7 +183,7 @@ impl Board {
 
         // Castling rights
         fen.push(" ".to_string());
-        fen.push(castling_as_string(self.gamestate.castling));
+        fen.push(self.castling_as_fen());
 
         // en_passant
         fen.push(" ".to_string());
@@ -163,6 +206,41 @@ impl Board {
     }
 }
 
+impl Board {
+    // Castling rights in X-FEN: KQkq for the outermost rooks, which is the
+    // same as standard FEN in standard chess, and the rook's file otherwise.
+    pub fn castling_as_fen(&self) -> String {
+        let mut castling = String::new();
+
+        for side in [Sides::WHITE, Sides::BLACK] {
+            for (wing, letter) in [(Wings::KING, 'k'), (Wings::QUEEN, 'q')] {
+                if self.gamestate.castling & Castling::RIGHTS[side][wing] == 0 {
+                    continue;
+                }
+
+                let file = self.castling_rooks[side][wing] % 8;
+                let c = if outer_rook(self, side, wing) == Some(file) {
+                    letter
+                } else {
+                    (b'a' + file as u8) as char
+                };
+
+                castling.push(if side == Sides::WHITE {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                });
+            }
+        }
+
+        if castling.is_empty() {
+            castling.push(DASH);
+        }
+
+        castling
+    }
+}
+
 fn pieces(board: &mut Board, part: &str) -> bool {
     let mut rank = Ranks::R8 as usize;
     let mut file = Files::A as usize;
@@ -170,6 +248,10 @@ fn pieces(board: &mut Board, part: &str) -> bool {
     let mut result = true;
 
     for c in part.chars() {
+        // A piece past the end of the rank.
+        if file >= 8 && LIST_OF_PIECES.contains(c) {
+            return false;
+        }
         let square = rank * 8 + file;
 
         match c {
@@ -191,8 +273,8 @@ fn pieces(board: &mut Board, part: &str) -> bool {
                 }
             }
             SPLITTER => {
-                result = file == 8;
-                rank -= 1;
+                result = file == 8 && rank > 0;
+                rank = rank.saturating_sub(1);
                 file = 0;
             }
             _ => result = false,
@@ -202,12 +284,13 @@ fn pieces(board: &mut Board, part: &str) -> bool {
             file += 1;
         }
 
-        if !result {
-            break;
+        if !result || file > 8 {
+            return false;
         }
     }
 
-    result
+    // All eight ranks must be complete.
+    result && rank == Ranks::R1 && file == 8
 }
 
 fn color(board: &mut Board, part: &str) -> bool {
@@ -230,26 +313,96 @@ fn color(board: &mut Board, part: &str) -> bool {
     result
 }
 
+// Castling rights can be given as KQkq, Shredder-FEN (the files of the
+// castling rooks, such as HAha) or X-FEN (KQkq for the outermost rooks, a
+// file for any other rook). The rights are tied to the rook's square.
 fn castling(board: &mut Board, part: &str) -> bool {
     let length = part.len();
     let mut correct = 0;
 
+    if length == 1 && part.starts_with(DASH) {
+        return true;
+    }
+
     if (1..=4).contains(&length) {
         for c in part.chars() {
-            match c {
-                'K' => board.gamestate.castling |= Castling::WK,
-                'Q' => board.gamestate.castling |= Castling::WQ,
-                'k' => board.gamestate.castling |= Castling::BK,
-                'q' => board.gamestate.castling |= Castling::BQ,
-                _ => (),
+            let side = if c.is_ascii_uppercase() {
+                Sides::WHITE
+            } else {
+                Sides::BLACK
+            };
+
+            let rook = match c.to_ascii_lowercase() {
+                'k' => outer_rook(board, side, Wings::KING),
+                'q' => outer_rook(board, side, Wings::QUEEN),
+                'a'..='h' => Some(c.to_ascii_lowercase() as Square - 'a' as Square),
+                _ => None,
+            };
+
+            if let Some(file) = rook {
+                if set_castling_rook(board, side, file) {
+                    correct += 1;
+                }
             }
-            correct += 1;
         }
     }
 
     (length >= 1) && (correct == length)
 }
 
+// Find the file of the rook furthest away from the king on the given wing.
+fn outer_rook(board: &Board, side: Side, wing: usize) -> Option<Square> {
+    let rank = if side == Sides::WHITE {
+        Ranks::R1
+    } else {
+        Ranks::R8
+    };
+    let king = board.bb_pieces[side][Pieces::KING].trailing_zeros() as Square;
+    let rooks = board.bb_pieces[side][Pieces::ROOK];
+    let king_file = king % 8;
+    let has_rook = |file: &Square| (rooks & BB_SQUARES[rank * 8 + file]) > 0;
+
+    if wing == Wings::KING {
+        ((king_file + 1)..=Files::H).rev().find(has_rook)
+    } else {
+        (Files::A..king_file).find(has_rook)
+    }
+}
+
+// Grant the castling right belonging to the rook on the given file. The
+// king and that rook must both be on the back rank.
+fn set_castling_rook(board: &mut Board, side: Side, file: Square) -> bool {
+    let rank = if side == Sides::WHITE {
+        Ranks::R1
+    } else {
+        Ranks::R8
+    };
+    let rook = rank * 8 + file;
+    let bb_king = board.bb_pieces[side][Pieces::KING];
+    let king = bb_king.trailing_zeros() as Square;
+    let king_on_rank = bb_king.count_ones() == 1 && king / 8 == rank;
+    let rook_on_square = (board.bb_pieces[side][Pieces::ROOK] & BB_SQUARES[rook]) > 0;
+
+    if !king_on_rank || !rook_on_square || file == king % 8 {
+        return false;
+    }
+
+    let wing = if file > king % 8 {
+        Wings::KING
+    } else {
+        Wings::QUEEN
+    };
+    let right = Castling::RIGHTS[side][wing];
+
+    board.gamestate.castling |= right;
+    board.castling_rooks[side][wing] = rook;
+    board.castling_perms[rook] &= !right;
+    board.castling_perms[king] &=
+        !(Castling::RIGHTS[side][Wings::KING] | Castling::RIGHTS[side][Wings::QUEEN]);
+
+    true
+}
+
 fn enpassant(board: &mut Board, part: &str) -> bool {
     let length = part.len();
 
@@ -298,16 +451,255 @@ fn fullmovenumber(board: &mut Board, part: &str) -> bool {
     let length = part.len();
 
     let mut result = false;
-    if length >= 1 || length <= 4 {
+    if (1..=4).contains(&length) {
         if let Ok(x) = part.parse::<u16>() {
             if x < MAX_GAME_MOVES as u16 {
                 board.gamestate.fullmove_number = x;
                 result = true;
             }
-        } else {
-            println!("Could not parse string to u16");
         }
     }
 
     result
 }
+
+// Check the pieces of a position that was read: one king per side, no
+// pawns on the first or last rank, and no more pieces than the pawns that
+// are gone can have promoted to.
+fn check_pieces(board: &Board) -> FenResult {
+    let back_ranks = BB_RANKS[Ranks::R1] | BB_RANKS[Ranks::R8];
+
+    for side in [Sides::WHITE, Sides::BLACK] {
+        let bb = &board.bb_pieces[side];
+        let count = |piece: usize| bb[piece].count_ones();
+
+        if count(Pieces::KING) != 1 {
+            return Err(FenError::KingCount(side, count(Pieces::KING)));
+        }
+
+        let pawns = bb[Pieces::PAWN] & back_ranks;
+        if pawns > 0 {
+            return Err(FenError::PawnOnBackRank(pawns.trailing_zeros() as Square));
+        }
+
+        let promoted = count(Pieces::QUEEN).saturating_sub(1)
+            + count(Pieces::ROOK).saturating_sub(2)
+            + count(Pieces::BISHOP).saturating_sub(2)
+            + count(Pieces::KNIGHT).saturating_sub(2);
+        if count(Pieces::PAWN) + promoted > 8 {
+            return Err(FenError::TooManyPieces(side));
+        }
+    }
+
+    Ok(())
+}
+
+// Check the position as a whole: the en-passant square must be right
+// behind a pawn that just moved two squares, and the side that just moved
+// can't have left its king in check.
+fn check_position(board: &Board) -> FenResult {
+    let us = board.side_to_move();
+    let them = us ^ 1;
+    let occupancy = board.occupancy();
+
+    if let Some(ep) = board.gamestate.en_passant {
+        let ep = ep as Square;
+        let (ep_squares, pawn, from) = if us == Sides::WHITE {
+            (EP_SQUARES_BLACK, ep - 8, ep + 8)
+        } else {
+            (EP_SQUARES_WHITE, ep + 8, ep - 8)
+        };
+
+        let plausible = ep_squares.contains(&ep)
+            && board.bb_pieces[them][Pieces::PAWN] & BB_SQUARES[pawn] > 0
+            && occupancy & (BB_SQUARES[ep] | BB_SQUARES[from]) == 0;
+        if !plausible {
+            return Err(FenError::EnPassantSquare(ep));
+        }
+    }
+
+    let king = board.bb_pieces[them][Pieces::KING].trailing_zeros() as Square;
+    if attacked(board, us, king) {
+        return Err(FenError::OpponentInCheck);
+    }
+
+    Ok(())
+}
+
+// Determine if a square is attacked by 'attacker'. The move generator
+// isn't at hand while reading a FEN, so the board is walked square by
+// square from the attacked square outward.
+fn attacked(board: &Board, attacker: Side, square: Square) -> bool {
+    #[rustfmt::skip]
+    const KNIGHT: [(i8, i8); 8] = [
+        (1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2),
+    ];
+    #[rustfmt::skip]
+    const KING: [(i8, i8); 8] = [
+        (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1),
+    ];
+
+    let pieces = &board.bb_pieces[attacker];
+    let occupancy = board.occupancy();
+    let (file, rank) = ((square % 8) as i8, (square / 8) as i8);
+
+    // The square 'steps' times (df, dr) away, if it is on the board.
+    let step = |(df, dr): (i8, i8), steps: i8| {
+        let (f, r) = (file + df * steps, rank + dr * steps);
+        let on_board = (0..NrOf::FILES as i8).contains(&f) && (0..NrOf::RANKS as i8).contains(&r);
+        on_board.then(|| BB_SQUARES[(r * 8 + f) as usize])
+    };
+    let any = |offsets: &[(i8, i8)], piece: usize| {
+        offsets
+            .iter()
+            .any(|&o| step(o, 1).is_some_and(|bb| bb & pieces[piece] > 0))
+    };
+
+    // Pawns attack the square from the rank behind it, as seen from the
+    // attacker.
+    let behind = if attacker == Sides::WHITE { -1 } else { 1 };
+    if any(&[(-1, behind), (1, behind)], Pieces::PAWN)
+        || any(&KNIGHT, Pieces::KNIGHT)
+        || any(&KING, Pieces::KING)
+    {
+        return true;
+    }
+
+    // Sliders: walk each ray up to the first piece.
+    let straight = pieces[Pieces::ROOK] | pieces[Pieces::QUEEN];
+    let diagonal = pieces[Pieces::BISHOP] | pieces[Pieces::QUEEN];
+    KING.iter().enumerate().any(|(i, &direction)| {
+        let sliders = if i % 2 == 0 { straight } else { diagonal };
+        let first = (1..8)
+            .map_while(|steps| step(direction, steps))
+            .find(|bb| bb & occupancy > 0);
+        first.is_some_and(|bb| bb & sliders > 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FenError, Squares};
+    use crate::{
+        board::Board,
+        defs::{Sides, FEN_KIWIPETE_POSITION, FEN_START_POSITION},
+        movegen::defs::algebraic_from_str,
+    };
+
+    fn read(fen: &str) -> Result<String, FenError> {
+        let mut board = Board::new();
+        board.read_fen(Some(fen)).map(|_| board.create_fen())
+    }
+
+    fn error(fen: &str) -> FenError {
+        read(fen).expect_err(fen)
+    }
+
+    #[test]
+    fn reads_positions() {
+        for fen in [
+            FEN_START_POSITION,
+            FEN_KIWIPETE_POSITION,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9",
+            "4k3/8/8/8/8/8/8/4K2R b K - 99 2047",
+        ] {
+            assert_eq!(read(fen).as_deref(), Ok(fen));
+        }
+
+        // Shredder-FEN is written as X-FEN, and an em dash as a dash.
+        assert_eq!(
+            read("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9"),
+            read("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9")
+        );
+        assert_eq!(
+            read("4k3/8/8/8/8/8/8/4K3 w – – 0 1").as_deref(),
+            Ok("4k3/8/8/8/8/8/8/4K3 w - - 0 1")
+        );
+    }
+
+    #[test]
+    fn rejects_parts() {
+        assert_eq!(error("8/8/8/8 w - -"), FenError::PartCount(4));
+
+        for pieces in [
+            "4k3/8/8/8/8/8/8/4K4",
+            "4k3/8/8/8/8/8/8/4K2",
+            "4k3/8/8/8/8/8/4K3",
+            "4k3/8/8/8/8/8/8/8/4K3",
+            "4k3/8/8/8/8/8/8/4X3",
+        ] {
+            let fen = format!("{pieces} w - - 0 1");
+            assert_eq!(error(&fen), FenError::Pieces(pieces.to_string()));
+        }
+
+        let fen = "4k3/8/8/8/8/8/8/4K3";
+        assert_eq!(
+            error(&format!("{fen} x - - 0 1")),
+            FenError::Color("x".to_string())
+        );
+        assert_eq!(
+            error(&format!("{fen} w K - 0 1")),
+            FenError::Castling("K".to_string())
+        );
+        assert_eq!(
+            error(&format!("{fen} w - e4 0 1")),
+            FenError::EnPassant("e4".to_string())
+        );
+        assert_eq!(
+            error(&format!("{fen} w - - 100 1")),
+            FenError::HalfmoveClock("100".to_string())
+        );
+        for number in ["x", "00001", "2048"] {
+            assert_eq!(
+                error(&format!("{fen} w - - 0 {number}")),
+                FenError::FullmoveNumber(number.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_pieces() {
+        assert_eq!(
+            error("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::KingCount(Sides::BLACK, 0)
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            FenError::KingCount(Sides::WHITE, 2)
+        );
+        assert_eq!(
+            error("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::PawnOnBackRank(Squares::H8)
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/PPPPPPPP/QQ2K3 w - - 0 1"),
+            FenError::TooManyPieces(Sides::WHITE)
+        );
+        assert!(read("4k3/8/8/8/8/8/PPPPPPP1/QQ2K3 w - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn rejects_positions() {
+        let e6 = algebraic_from_str("e6").expect("square");
+        assert_eq!(
+            error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1"),
+            FenError::EnPassantSquare(e6)
+        );
+        assert_eq!(
+            error("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::OpponentInCheck
+        );
+        assert!(read("4k3/4R3/8/8/8/8/8/4K3 b - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn keeps_board_on_error() {
+        let mut board = Board::new();
+        board
+            .read_fen(Some(FEN_KIWIPETE_POSITION))
+            .expect("valid FEN");
+        assert!(board.read_fen(Some("8/8/8/8/8/8/8/4K3 w - - 0 1")).is_err());
+        assert_eq!(board.create_fen(), FEN_KIWIPETE_POSITION);
+    }
+}
@@ -1,34 +1,12 @@
 use super::{
-    defs::{Pieces, Squares, BB_SQUARES},
+    defs::{Pieces, BB_SQUARES},
     Board,
 };
 use crate::{
-    defs::{Castling, NrOf, Piece, Side, Sides, Square},
+    defs::{Piece, Side, Sides, Square},
     movegen::{defs::Move, MoveGenerator},
 };
 
-// Castling Permissions Per Square
-type CPSquare = [u8; NrOf::SQUARES];
-const CASTLING_PERMS: CPSquare = castling_permissions_per_square();
-const fn castling_permissions_per_square() -> CPSquare {
-    // First set all squares grant all castling permissions. This means
-    // moving a piece on such square doesn't have any effect on castling
-    // permissions.
-    let mut cp: CPSquare = [Castling::ALL; NrOf::SQUARES];
-
-    // Now disable castling permissions when moving pieces on certain
-    // squares. For example, when the piece (rook) on A1 moves, disable
-    // white castling to the queenside.
-    cp[Squares::A1] &= !Castling::WQ;
-    cp[Squares::E1] &= !Castling::WK & !Castling::WQ;
-    cp[Squares::H1] &= !Castling::WK;
-    cp[Squares::A8] &= !Castling::BQ;
-    cp[Squares::E8] &= !Castling::BK & !Castling::BQ;
-    cp[Squares::H8] &= !Castling::BK;
-
-    cp
-}
-
 impl Board {
     pub fn make_move(&mut self, m: Move, mg: &MoveGenerator) -> bool {
         let mut current_game_state = self.gamestate;
@@ -66,12 +44,21 @@ impl Board {
             self.gamestate.halfclock_move = 0;
 
             if captured == Pieces::ROOK && castling_perm {
-                self.update_castling_perm(self.gamestate.castling & CASTLING_PERMS[to]);
+                self.update_castling_perm(self.gamestate.castling & self.castling_perms[to]);
             }
         }
 
-        // Make move if NOT a pawn
-        if piece != Pieces::PAWN {
+        // Castling is encoded as the king taking its own rook. Take both
+        // pieces off the board first: in Chess960 the king or rook may end
+        // up on the square the other one started from.
+        if castling {
+            let (king_to, rook_to) = Board::castling_targets(player, m.castling_wing());
+            self.remove_piece(player, Pieces::KING, from);
+            self.remove_piece(player, Pieces::ROOK, to);
+            self.put_piece(player, Pieces::KING, king_to);
+            self.put_piece(player, Pieces::ROOK, rook_to);
+        } else if piece != Pieces::PAWN {
+            // Make move if NOT a pawn
             self.move_piece(player, piece, from, to);
         } else {
             // it is a pawn move also check for promotion and reset halfclock_move
@@ -91,18 +78,7 @@ impl Board {
 
         // check if king / rook moves from start square if so remove perm
         if (piece == Pieces::KING || piece == Pieces::ROOK) & castling_perm {
-            self.update_castling_perm(self.gamestate.castling & CASTLING_PERMS[from]);
-        }
-
-        // if castling move rook aswell
-        if castling {
-            match to {
-                Squares::G1 => self.move_piece(player, Pieces::ROOK, Squares::H1, Squares::F1),
-                Squares::C1 => self.move_piece(player, Pieces::ROOK, Squares::A1, Squares::D1),
-                Squares::G8 => self.move_piece(player, Pieces::ROOK, Squares::H8, Squares::F8),
-                Squares::C8 => self.move_piece(player, Pieces::ROOK, Squares::H8, Squares::F8),
-                _ => panic!("Error moving rook"),
-            }
+            self.update_castling_perm(self.gamestate.castling & self.castling_perms[from]);
         }
 
         // swap player
@@ -123,6 +99,36 @@ impl Board {
     }
 }
 
+// A null move passes the turn to the opponent without moving a piece. The
+// search uses it to find out what the opponent would do if it could move
+// twice in a row. The game state is pushed onto the history like that of a
+// normal move, with an empty move as the move played, so repetition
+// detection can tell where the null move was made.
+impl Board {
+    pub fn make_null_move(&mut self) {
+        let mut current_game_state = self.gamestate;
+        current_game_state.next_move = Move::new(0);
+        self.history.push(current_game_state);
+
+        // Passing doesn't leave a pawn to capture en passant.
+        if self.gamestate.en_passant.is_some() {
+            self.clear_ep_square();
+        }
+
+        self.gamestate.halfclock_move += 1;
+        if self.side_to_move() == Sides::BLACK {
+            self.gamestate.fullmove_number += 1;
+        }
+
+        self.swap_side();
+    }
+
+    // Nothing was moved, so restoring the game state is all there is to do.
+    pub fn unmake_null_move(&mut self) {
+        self.gamestate = self.history.pop();
+    }
+}
+
 // Unmake() reverses the last move. The game state is restored by popping it
 // from the history array, all variables at once.
 impl Board {
@@ -146,25 +152,20 @@ impl Board {
         let en_passant = m.en_passant();
 
         // Moving backwards...
-        if promoted == Pieces::NONE {
+        if castling {
+            // Put the king and the rook back on their own squares.
+            let (king_to, rook_to) = Board::castling_targets(player, m.castling_wing());
+            remove_piece(self, player, Pieces::KING, king_to);
+            remove_piece(self, player, Pieces::ROOK, rook_to);
+            put_piece(self, player, Pieces::KING, from);
+            put_piece(self, player, Pieces::ROOK, to);
+        } else if promoted == Pieces::NONE {
             reverse_move(self, player, piece, to, from);
         } else {
             remove_piece(self, player, promoted, to);
             put_piece(self, player, Pieces::PAWN, from);
         }
 
-        // The king's move was already undone as a normal move.
-        // Now undo the correct castling rook move.
-        if castling {
-            match to {
-                Squares::G1 => reverse_move(self, player, Pieces::ROOK, Squares::F1, Squares::H1),
-                Squares::C1 => reverse_move(self, player, Pieces::ROOK, Squares::D1, Squares::A1),
-                Squares::G8 => reverse_move(self, player, Pieces::ROOK, Squares::F8, Squares::H8),
-                Squares::C8 => reverse_move(self, player, Pieces::ROOK, Squares::D8, Squares::A8),
-                _ => panic!("Error: Reversing castling rook move."),
-            };
-        }
-
         // If a piece was captured, put it back onto the to-square
         if captured != Pieces::NONE {
             put_piece(self, opponent, captured, to);
@@ -195,5 +196,4 @@ fn put_piece(board: &mut Board, side: Side, piece: Piece, square: Square) {
 fn reverse_move(board: &mut Board, side: Side, piece: Piece, remove: Square, put: Square) {
     remove_piece(board, side, piece, remove);
     put_piece(board, side, piece, put);
-    board.update_castling_perm(board.gamestate.castling & CASTLING_PERMS[remove]);
 }
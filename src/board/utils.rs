@@ -1,6 +1,6 @@
 use super::{defs::Location, Board};
 use crate::{
-    board::defs::Ranks,
+    board::defs::{Files, Ranks, Wings},
     defs::{Side, Sides, Square},
 };
 
@@ -34,4 +34,21 @@ impl Board {
             Ranks::R1
         }
     }
+
+    // Squares the king and the rook end up on after castling to the given
+    // wing. These are the same in standard chess and Chess960.
+    pub fn castling_targets(side: Side, wing: usize) -> (Square, Square) {
+        let rank = if side == Sides::WHITE {
+            Ranks::R1
+        } else {
+            Ranks::R8
+        };
+        let (king, rook) = if wing == Wings::KING {
+            (Files::G, Files::F)
+        } else {
+            (Files::C, Files::D)
+        };
+
+        (rank * 8 + king, rank * 8 + rook)
+    }
 }
@@ -4,14 +4,19 @@ use crossbeam_channel::Sender;
 
 use crate::{
     board::Board,
-    engine::defs::Information,
+    defs::EngineError,
+    engine::defs::{EngineOption, Information},
     movegen::defs::Move,
     search::defs::{PerftSummary, SearchCurrentMove, SearchStats, SearchSummary},
 };
 
-use self::uci::UciReport;
+use self::{uci::UciReport, xboard::XBoardReport};
 
+pub mod console;
+pub mod json;
+pub mod transport;
 pub mod uci;
+pub mod xboard;
 
 // Defines the public functions a Comm module must implement.
 pub trait IComm {
@@ -19,7 +24,7 @@ pub trait IComm {
         &mut self,
         report_tx: Sender<Information>,
         board: Arc<Mutex<Board>>,
-        // options: Arc<Vec<EngineOption>>,
+        options: Arc<Vec<EngineOption>>,
     );
     fn send(&self, msg: CommControl);
     fn wait_for_shutdown(&mut self);
@@ -33,11 +38,16 @@ pub enum CommControl {
     Quit,                              // Quit the Comm module.
     Identify,                          // Transmit identification of the engine.
     Ready,                             // Transmit that the engine is ready.
+    Pong(u32),                         // Transmit reply to a ping.
     SearchSummary(SearchSummary),      // Transmit search information.
     SearchCurrMove(SearchCurrentMove), // Transmit currently considered move.
     SearchStats(SearchStats),          // Transmit search Statistics.
     InfoString(String),                // Transmit general information.
-    BestMove(Move),                    // Transmit the engine's best move.
+    BestMove(Move, Option<Move>),      // Transmit the engine's best and ponder move.
+    IllegalMove(String),               // Transmit that a received move is illegal.
+    Error(EngineError),                // Transmit an error the user should see.
+    Hint(Move),                        // Transmit a move suggestion for the user.
+    GameResult(String),                // Transmit the result when the game is over.
     PerftScore(PerftSummary),          // Transmit perft score
     SolvePuzzles,
     // Output to screen when running in a terminal window.
@@ -51,6 +61,7 @@ pub enum CommControl {
 #[derive(PartialEq, Clone, Debug)]
 pub enum CommReport {
     Uci(UciReport),
+    XBoard(XBoardReport),
 }
 
 impl CommReport {
@@ -0,0 +1,449 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{
+    board::Board,
+    defs::{About, Sides, MAX_PLY},
+    engine::defs::{EngineOption, Information},
+    extra::{
+        parse::algebraic_move_to_number,
+        pgn::{PgnGame, PgnReader},
+        print,
+        san::{line_to_san, move_to_san, san_to_move},
+    },
+    movegen::{defs::Move, MoveGenerator},
+    search::defs::{Bound, SearchSummary, INF},
+};
+
+use super::{
+    transport::Transport,
+    xboard::{TimeControl, XBoardReport},
+    CommControl, CommReport, IComm,
+};
+
+// Time the engine takes for each move when playing in the console.
+const CONSOLE_MOVE_TIME: u128 = 3_000;
+
+const HELP: &str = "\
+Commands:
+  <move>       Play a move, such as e4, Nf3, exd5, O-O or e2e4
+  go           Let the engine play the side to move
+  new          Start a new game
+  undo         Take back your last move
+  hint         Suggest a move
+  flip         Turn the board around
+  level <n>    Let the engine search no deeper than n moves
+  board        Show the board
+  pgn          Show the game in PGN
+  load <file>  Continue the first game in a PGN file
+  help         Show this help
+  quit         Leave the program";
+
+// The console lets a user play against the engine in a terminal. The
+// engine keeps the game, just as it does under XBoard, so the commands
+// the user types are translated into XBoard reports.
+pub struct Console {
+    control_handle: Option<JoinHandle<()>>,
+    report_handle: Option<JoinHandle<()>>,
+    control_tx: Option<Sender<CommControl>>,
+    transport: Transport,     // Connection to the user.
+    flipped: Arc<AtomicBool>, // Show the board from Black's side.
+}
+
+impl Console {
+    pub fn new(transport: Transport) -> Self {
+        Self {
+            control_handle: None,
+            report_handle: None,
+            control_tx: None,
+            transport,
+            flipped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl IComm for Console {
+    // There's no GUI, so the options are not used.
+    fn init(
+        &mut self,
+        info_sender: Sender<Information>,
+        board: Arc<Mutex<Board>>,
+        _options: Arc<Vec<EngineOption>>,
+    ) {
+        self.report_thread(info_sender, Arc::clone(&board));
+        self.control_thread(board);
+    }
+
+    // Send messages to the control thread
+    fn send(&self, msg: CommControl) {
+        if let Some(tx) = &self.control_tx {
+            tx.send(msg).expect("Failed sending message {msg}");
+        }
+    }
+
+    // After the engine sends 'quit' to the control thread, it will call
+    // wait_for_shutdown() and then wait here until shutdown is completed.
+    fn wait_for_shutdown(&mut self) {
+        if let Some(h) = self.report_handle.take() {
+            h.join().expect("error stopping thread");
+        }
+
+        if let Some(h) = self.control_handle.take() {
+            h.join().expect("error stopping thread");
+        }
+    }
+
+    // This function just returns the name of the communication protocol.
+    fn get_protocol_name(&self) -> &'static str {
+        "console"
+    }
+}
+
+// Implement report thread
+impl Console {
+    pub fn report_thread(&mut self, info_sender: Sender<Information>, board: Arc<Mutex<Board>>) {
+        let mut t_incoming_data = String::from("");
+        let t_info_sender = info_sender;
+        let t_transport = self.transport.clone();
+        let t_flipped = Arc::clone(&self.flipped);
+
+        let report_thread = thread::spawn(move || {
+            // Needed to find the moves the user enters in SAN.
+            let mg = MoveGenerator::new();
+            let mut quit = false;
+
+            // Play at a pace that suits a human opponent.
+            let tc = TimeControl::SecondsPerMove(CONSOLE_MOVE_TIME);
+            t_info_sender
+                .send(Information::Comm(CommReport::XBoard(
+                    XBoardReport::TimeControl(tc),
+                )))
+                .expect("Error sending message");
+
+            while !quit {
+                // Read the next command. Quit if the user went away.
+                let reports = match t_transport.read_line(&mut t_incoming_data) {
+                    0 => vec![XBoardReport::Quit],
+                    _ => Self::create_reports(
+                        &t_incoming_data,
+                        &board,
+                        &mg,
+                        &t_flipped,
+                        &t_transport,
+                    ),
+                };
+
+                for report in reports {
+                    quit = quit || report == XBoardReport::Quit;
+                    t_info_sender
+                        .send(Information::Comm(CommReport::XBoard(report)))
+                        .expect("Error sending message");
+                }
+
+                t_incoming_data = String::from("");
+            }
+        });
+
+        self.report_handle = Some(report_thread);
+    }
+}
+
+// Implement control thread
+impl Console {
+    pub fn control_thread(&mut self, board: Arc<Mutex<Board>>) {
+        let (control_tx, control_rx) = crossbeam_channel::unbounded::<CommControl>();
+
+        let t_board = Arc::clone(&board);
+        let t_out = self.transport.clone();
+        let t_flipped = Arc::clone(&self.flipped);
+        let control_thread = thread::spawn(move || {
+            // Needed to write moves in SAN.
+            let mg = MoveGenerator::new();
+            let mut quit = false;
+
+            t_out.write_line(&format!(
+                "{} {} by {}. Type 'help' for a list of commands.",
+                About::ENGINE,
+                About::VERSION,
+                About::AUTHOR
+            ));
+
+            while !quit {
+                let control = control_rx.recv().expect("error receiving message");
+
+                match control {
+                    CommControl::Quit => quit = true,
+                    CommControl::Update | CommControl::PrintBoard => {
+                        Self::print_board(&t_board, &t_flipped, &t_out)
+                    }
+                    CommControl::BestMove(m, _) => {
+                        let m = Self::played_move_as_san(m, &t_board, &mg);
+                        t_out.write_line(&format!("My move: {m}"));
+                        Self::print_board(&t_board, &t_flipped, &t_out);
+                    }
+                    CommControl::SearchSummary(summary) => {
+                        Self::search_summary(&summary, &t_board, &mg, &t_out)
+                    }
+                    CommControl::InfoString(info) => t_out.write_line(&info),
+                    CommControl::IllegalMove(m) => t_out.write_line(&format!("Illegal move: {m}")),
+                    CommControl::Error(e) => t_out.write_line(&format!("Error: {e}")),
+                    CommControl::Hint(m) => {
+                        let mut board = t_board.lock().expect("Error locking board").clone();
+                        let m = move_to_san(&mut board, &mg, m);
+                        t_out.write_line(&format!("Hint: {m}"));
+                    }
+                    CommControl::GameResult(result) => {
+                        t_out.write_line(&format!("Game over: {result}"))
+                    }
+
+                    // Not used by the console.
+                    CommControl::Identify
+                    | CommControl::Ready
+                    | CommControl::Pong(_)
+                    | CommControl::SearchCurrMove(_)
+                    | CommControl::SearchStats(_)
+                    | CommControl::PerftScore(_)
+                    | CommControl::SolvePuzzles
+                    | CommControl::PrintHistory
+                    | CommControl::PrintHelp => (),
+                }
+            }
+        });
+
+        self.control_handle = Some(control_thread);
+        self.control_tx = Some(control_tx);
+    }
+}
+
+// Private functions for Console
+impl Console {
+    // Translate the user's command into the reports for the engine. Help
+    // and mistakes are answered right away.
+    fn create_reports(
+        message: &str,
+        board: &Arc<Mutex<Board>>,
+        mg: &MoveGenerator,
+        flipped: &AtomicBool,
+        out: &Transport,
+    ) -> Vec<XBoardReport> {
+        let parts: Vec<&str> = message.split_whitespace().collect();
+
+        match parts[..] {
+            [] => vec![],
+            ["quit"] | ["exit"] => vec![XBoardReport::Quit],
+            ["new"] => vec![XBoardReport::New, XBoardReport::Board],
+            ["go"] => vec![XBoardReport::Go],
+            ["undo"] => vec![XBoardReport::Remove, XBoardReport::Board],
+            ["hint"] => vec![XBoardReport::Hint],
+            ["board"] | ["d"] => vec![XBoardReport::Board],
+            ["flip"] => {
+                flipped.fetch_xor(true, Ordering::Relaxed);
+                vec![XBoardReport::Board]
+            }
+            ["level", depth] => match depth.parse::<i8>() {
+                Ok(d) if (1..=MAX_PLY).contains(&d) => vec![XBoardReport::Sd(d)],
+                _ => {
+                    out.write_line(&format!("Level must be between 1 and {MAX_PLY}"));
+                    vec![]
+                }
+            },
+            ["help"] => {
+                out.write_line(HELP);
+                vec![]
+            }
+            ["pgn"] => {
+                out.write_line(Self::game_as_pgn(board, mg).trim_end());
+                vec![]
+            }
+            ["load", ..] => {
+                let file = message.trim()["load".len()..].trim();
+                Self::load_game(file, board, mg, out)
+            }
+            [m] => match Self::parse_move(m, board, mg) {
+                Some(m) => vec![XBoardReport::UserMove(m)],
+                None => {
+                    out.write_line(&format!("Illegal move or unknown command: {m}"));
+                    vec![]
+                }
+            },
+            _ => {
+                out.write_line(&format!("Unknown command: {}", message.trim()));
+                vec![]
+            }
+        }
+    }
+
+    // The game up to now. The moves are in the board's history, so the
+    // start position is found by taking all of them back.
+    fn game_as_pgn(board: &Arc<Mutex<Board>>, mg: &MoveGenerator) -> String {
+        let mut board = board.lock().expect("Error locking board").clone();
+        let result = Self::result(&mut board, mg);
+
+        let moves: Vec<Move> = (0..board.history.len())
+            .map(|i| board.history.get_ref(i).next_move)
+            .collect();
+        for _ in moves.iter() {
+            board.unmake();
+        }
+
+        let mut game = PgnGame::from_moves(&board, &moves, mg);
+        game.set_tag("Event", "Console game");
+        game.set_tag("Result", result);
+        game.to_string()
+    }
+
+    // The result if the game is over, or "*" if it isn't.
+    fn result(board: &mut Board, mg: &MoveGenerator) -> &'static str {
+        if !mg.legal_moves(board).is_empty() {
+            return "*";
+        }
+
+        let side = board.side_to_move();
+        let in_check = mg.square_attacked(board, board.side_to_not_move(), board.king_square(side));
+        match (in_check, side) {
+            (false, _) => "1/2-1/2",
+            (true, Sides::WHITE) => "0-1",
+            (true, _) => "1-0",
+        }
+    }
+
+    // Set up the position at the end of the first game in a PGN file. The
+    // engine then plays the side that is not to move.
+    fn load_game(
+        file: &str,
+        board: &Arc<Mutex<Board>>,
+        mg: &MoveGenerator,
+        out: &Transport,
+    ) -> Vec<XBoardReport> {
+        let game = File::open(file)
+            .map_err(|e| e.to_string())
+            .and_then(|f| match PgnReader::new(BufReader::new(f)).next() {
+                Some(Ok(game)) => Ok(game),
+                Some(Err(e)) => Err(e.to_string()),
+                None => Err(String::from("no game found")),
+            });
+
+        // Check the whole game before changing the position.
+        let replayed = game.and_then(|g| {
+            let start = g.start_position().map_err(|e| e.to_string())?;
+            let (_, moves) = g.replay(mg).map_err(|e| e.to_string())?;
+            Ok((start, moves))
+        });
+
+        match replayed {
+            Ok((start, moves)) => {
+                let chess960 = board.lock().expect("Error locking board").chess960;
+                let mut reports = vec![
+                    XBoardReport::Force,
+                    XBoardReport::SetBoard(start.create_fen()),
+                ];
+                reports.extend(
+                    moves
+                        .iter()
+                        .map(|m| XBoardReport::UserMove(m.as_notation(chess960))),
+                );
+                reports.extend([XBoardReport::PlayOther, XBoardReport::Board]);
+                out.write_line(&format!("Loaded {} moves from {file}", moves.len()));
+                reports
+            }
+            Err(e) => {
+                out.write_line(&format!("Can't load {file}: {e}"));
+                vec![]
+            }
+        }
+    }
+
+    // The engine takes moves in coordinate notation. A move in SAN is
+    // looked up in the current position and converted.
+    fn parse_move(m: &str, board: &Arc<Mutex<Board>>, mg: &MoveGenerator) -> Option<String> {
+        if algebraic_move_to_number(m).is_ok() {
+            return Some(m.to_string());
+        }
+
+        let mut board = board.lock().expect("Error locking board").clone();
+        san_to_move(&mut board, mg, m).map(|m| m.as_notation(board.chess960))
+    }
+
+    // The engine plays its move on the board before reporting it, so the
+    // move is taken back on a copy of the board to write it in SAN.
+    fn played_move_as_san(m: Move, board: &Arc<Mutex<Board>>, mg: &MoveGenerator) -> String {
+        let mut board = board.lock().expect("Error locking board").clone();
+        board.unmake();
+        move_to_san(&mut board, mg, m)
+    }
+
+    fn print_board(board: &Arc<Mutex<Board>>, flipped: &AtomicBool, out: &Transport) {
+        let board = board.lock().expect("Error locking board");
+        let flipped = flipped.load(Ordering::Relaxed);
+        let side = if board.side_to_move() == Sides::WHITE {
+            "White"
+        } else {
+            "Black"
+        };
+
+        out.write_line(print::position_for_player(&board, flipped).trim_end());
+        out.write_line(&format!("{side} to move"));
+    }
+
+    // The engine may already have played its move when the last lines of
+    // the search are shown. If the line doesn't start with a legal move,
+    // it is shown from the position before that move.
+    fn position_searched(board: &Arc<Mutex<Board>>, mg: &MoveGenerator, line: &[Move]) -> Board {
+        let mut board = board.lock().expect("Error locking board").clone();
+        let legal = line.first().is_none_or(|m| {
+            let legal_moves = mg.legal_moves(&mut board);
+            legal_moves.iter().any(|l| l.get_move() == m.get_move())
+        });
+
+        if !legal && board.history.len() > 0 {
+            board.unmake();
+        }
+        board
+    }
+
+    // Show what the engine is thinking: depth, score, time and the line
+    // it expects.
+    fn search_summary(
+        summary: &SearchSummary,
+        board: &Arc<Mutex<Board>>,
+        mg: &MoveGenerator,
+        out: &Transport,
+    ) {
+        let score = if summary.cp == -INF {
+            String::from("draw")
+        } else if summary.mate != 0 {
+            format!("mate {}", summary.mate)
+        } else {
+            format!("{}", summary.cp)
+        };
+
+        // A failed aspiration window only gives a bound on the score.
+        let score = match summary.bound {
+            Bound::Exact => score,
+            Bound::Lower => format!(">{score}"),
+            Bound::Upper => format!("<{score}"),
+        };
+
+        out.write_line(&format!(
+            "depth {:>2}  score {:>7}  time {:>6}  {}",
+            summary.depth,
+            score,
+            summary.time,
+            line_to_san(
+                &Self::position_searched(board, mg, &summary.pv),
+                mg,
+                &summary.pv
+            )
+            .join(" ")
+        ));
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::Sender;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::Board,
+    defs::{About, FEN_START_POSITION},
+    engine::defs::{EngineOption, EngineOptionName, Information},
+    movegen::defs::Move,
+    search::defs::{GameTime, PerftSummary, SearchMode, SearchParams, SearchStats, SearchSummary},
+};
+
+use super::{transport::Transport, uci::UciReport, CommControl, CommReport, IComm};
+
+// The JSON protocol carries the same commands as UCI, for programs that
+// would rather not parse text. Every command is a single JSON object on
+// one line, with its name in the "cmd" field:
+//
+// {"cmd": "position", "fen": "...", "moves": ["e2e4", "e7e5"]}
+// {"cmd": "go", "depth": 10}
+//
+// Because the commands mean the same thing, they are translated into UCI
+// reports, so the engine handles them as it handles UCI. Everything the
+// engine sends back is also a single-line JSON object, with its kind in
+// the "type" field.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum JsonCommand {
+    Uci,
+    Debug {
+        on: bool,
+    },
+    NewGame,
+    IsReady,
+    SetOption {
+        name: String,
+        #[serde(default)]
+        value: String,
+    },
+    Position {
+        fen: Option<String>, // Start position if absent.
+        #[serde(default)]
+        moves: Vec<String>,
+    },
+    Go(JsonGo),
+    Perft {
+        depth: i8,
+    },
+    PonderHit,
+    Stop,
+    Quit,
+    Board,
+}
+
+// The limits of "go", as in UCI. All of them are optional.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct JsonGo {
+    depth: Option<i8>,
+    movetime: Option<u128>,
+    nodes: Option<usize>,
+    mate: Option<u8>,
+    wtime: u128,
+    btime: u128,
+    winc: u128,
+    binc: u128,
+    movestogo: Option<usize>,
+    infinite: bool,
+    ponder: bool,
+    searchmoves: Vec<String>,
+}
+
+// Messages sent to the client.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonMessage<'a> {
+    Id {
+        name: &'a str,
+        version: &'a str,
+        author: &'a str,
+        options: &'a [EngineOption],
+    },
+    ReadyOk,
+    Summary {
+        #[serde(flatten)]
+        summary: &'a SearchSummary,
+        pv: Vec<String>,
+    },
+    Stats(&'a SearchStats),
+    CurrMove {
+        #[serde(rename = "move")]
+        curr_move: String,
+        number: u8,
+    },
+    BestMove {
+        #[serde(rename = "move")]
+        best_move: String,
+        ponder: Option<String>,
+    },
+    Perft(&'a PerftSummary),
+    Board {
+        fen: String,
+    },
+    Info {
+        message: &'a str,
+    },
+    Error {
+        message: &'a str,
+    },
+}
+
+pub struct Json {
+    control_handle: Option<JoinHandle<()>>,
+    report_handle: Option<JoinHandle<()>>,
+    control_tx: Option<Sender<CommControl>>,
+    transport: Transport, // Connection to the client.
+}
+
+impl Json {
+    pub fn new(transport: Transport) -> Self {
+        Self {
+            control_handle: None,
+            report_handle: None,
+            control_tx: None,
+            transport,
+        }
+    }
+}
+
+impl IComm for Json {
+    fn init(
+        &mut self,
+        info_sender: Sender<Information>,
+        board: Arc<Mutex<Board>>,
+        options: Arc<Vec<EngineOption>>,
+    ) {
+        self.report_thread(info_sender);
+        self.control_thread(board, options);
+    }
+
+    // Send messages to the control thread
+    fn send(&self, msg: CommControl) {
+        if let Some(tx) = &self.control_tx {
+            tx.send(msg).expect("Failed sending message {msg}");
+        }
+    }
+
+    // After the engine sends 'quit' to the control thread, it will call
+    // wait_for_shutdown() and then wait here until shutdown is completed.
+    fn wait_for_shutdown(&mut self) {
+        if let Some(h) = self.report_handle.take() {
+            h.join().expect("error stopping thread");
+        }
+
+        if let Some(h) = self.control_handle.take() {
+            h.join().expect("error stopping thread");
+        }
+    }
+
+    // This function just returns the name of the communication protocol.
+    fn get_protocol_name(&self) -> &'static str {
+        "json"
+    }
+}
+
+// Implement report thread
+impl Json {
+    pub fn report_thread(&mut self, info_sender: Sender<Information>) {
+        let mut t_incoming_data = String::from("");
+        let t_info_sender = info_sender;
+        let t_transport = self.transport.clone();
+
+        let report_thread = thread::spawn(move || {
+            let mut quit = false;
+
+            while !quit {
+                // Stop when the client closes its end of the connection.
+                let report = match t_transport.read_line(&mut t_incoming_data) {
+                    0 => Some(CommReport::Uci(UciReport::Quit)),
+                    _ => Self::create_report(&t_incoming_data, &t_transport),
+                };
+
+                if let Some(report) = report {
+                    t_info_sender
+                        .send(Information::Comm(report.clone()))
+                        .expect("Error sending message");
+
+                    quit = report == CommReport::Uci(UciReport::Quit);
+                }
+
+                t_incoming_data = String::from("");
+            }
+        });
+
+        self.report_handle = Some(report_thread);
+    }
+}
+
+// Implement control thread
+impl Json {
+    pub fn control_thread(&mut self, board: Arc<Mutex<Board>>, options: Arc<Vec<EngineOption>>) {
+        let (control_tx, control_rx) = crossbeam_channel::unbounded::<CommControl>();
+
+        let t_board = Arc::clone(&board);
+        let t_options = Arc::clone(&options);
+        let t_out = self.transport.clone();
+        let control_thread = thread::spawn(move || {
+            let mut quit = false;
+            while !quit {
+                let control = control_rx.recv().expect("error receiving message");
+
+                match control {
+                    CommControl::Identify => Self::send_message(
+                        &JsonMessage::Id {
+                            name: About::ENGINE,
+                            version: About::VERSION,
+                            author: About::AUTHOR,
+                            options: &t_options,
+                        },
+                        &t_out,
+                    ),
+                    CommControl::Ready => Self::send_message(&JsonMessage::ReadyOk, &t_out),
+                    CommControl::Quit => quit = true,
+                    CommControl::SearchSummary(summary) => {
+                        let chess960 = Self::chess960(&t_board);
+                        let pv = summary.pv.iter().map(|m| m.as_notation(chess960)).collect();
+                        let summary = &summary;
+                        Self::send_message(&JsonMessage::Summary { summary, pv }, &t_out);
+                    }
+                    CommControl::SearchStats(stats) => {
+                        Self::send_message(&JsonMessage::Stats(&stats), &t_out)
+                    }
+                    CommControl::SearchCurrMove(current) => Self::send_message(
+                        &JsonMessage::CurrMove {
+                            curr_move: current.curr_move.as_notation(Self::chess960(&t_board)),
+                            number: current.curr_move_number,
+                        },
+                        &t_out,
+                    ),
+                    CommControl::InfoString(message) => {
+                        Self::send_message(&JsonMessage::Info { message: &message }, &t_out)
+                    }
+                    CommControl::BestMove(best_move, ponder_move) => {
+                        Self::best_move(&best_move, ponder_move, &t_board, &t_out)
+                    }
+                    CommControl::IllegalMove(m) => {
+                        let message = format!("{m}: illegal move");
+                        Self::send_message(&JsonMessage::Error { message: &message }, &t_out)
+                    }
+                    CommControl::Error(e) => {
+                        let message = e.to_string();
+                        Self::send_message(&JsonMessage::Error { message: &message }, &t_out)
+                    }
+                    CommControl::PerftScore(summary) => {
+                        Self::send_message(&JsonMessage::Perft(&summary), &t_out)
+                    }
+                    CommControl::PrintBoard => {
+                        let fen = t_board.lock().expect("Error locking board").create_fen();
+                        Self::send_message(&JsonMessage::Board { fen }, &t_out)
+                    }
+
+                    // Not used by this protocol.
+                    CommControl::SolvePuzzles
+                    | CommControl::PrintHistory
+                    | CommControl::PrintHelp
+                    | CommControl::Pong(_)
+                    | CommControl::Hint(_)
+                    | CommControl::GameResult(_)
+                    | CommControl::Update => (),
+                }
+            }
+        });
+
+        self.control_handle = Some(control_thread);
+        self.control_tx = Some(control_tx);
+    }
+}
+
+// Private functions for Json
+impl Json {
+    // Translate a command into the UCI report with the same meaning. A line
+    // that isn't a valid command is answered with an error right away.
+    fn create_report(message: &str, transport: &Transport) -> Option<CommReport> {
+        let message = message.trim();
+        if message.is_empty() {
+            return None;
+        }
+
+        let command = match serde_json::from_str::<JsonCommand>(message) {
+            Ok(c) => c,
+            Err(e) => {
+                let message = format!("invalid command: {e}");
+                Self::send_message(&JsonMessage::Error { message: &message }, transport);
+                return None;
+            }
+        };
+
+        let report = match command {
+            JsonCommand::Uci => UciReport::Uci,
+            JsonCommand::Debug { on } => UciReport::Debug(on),
+            JsonCommand::NewGame => UciReport::UciNewGame,
+            JsonCommand::IsReady => UciReport::IsReady,
+            JsonCommand::SetOption { name, value } => {
+                UciReport::SetOption(EngineOptionName::new(&name, value))
+            }
+            JsonCommand::Position { fen, moves } => {
+                let fen = fen.unwrap_or_else(|| String::from(FEN_START_POSITION));
+                UciReport::Position(fen, moves)
+            }
+            JsonCommand::Go(go) => Self::go(go),
+            JsonCommand::Perft { depth } => UciReport::GoPerft(depth),
+            JsonCommand::PonderHit => UciReport::PonderHit,
+            JsonCommand::Stop => UciReport::Stop,
+            JsonCommand::Quit => UciReport::Quit,
+            JsonCommand::Board => UciReport::Board,
+        };
+
+        Some(CommReport::Uci(report))
+    }
+
+    // Collect the limits of "go" into search parameters, the same way the
+    // UCI "go" command does.
+    fn go(go: JsonGo) -> UciReport {
+        let mut sp = SearchParams::new();
+        let game_time = GameTime::new(go.wtime, go.btime, go.winc, go.binc, go.movestogo);
+
+        if go.infinite {
+            sp.add_mode(SearchMode::Infinite);
+        }
+        if let Some(depth) = go.depth {
+            sp.depth = depth;
+            sp.add_mode(SearchMode::Depth);
+        }
+        if let Some(move_time) = go.movetime {
+            sp.move_time = move_time;
+            sp.add_mode(SearchMode::MoveTime);
+        }
+        if let Some(nodes) = go.nodes {
+            sp.nodes = nodes;
+            sp.add_mode(SearchMode::Nodes);
+        }
+        if let Some(mate) = go.mate {
+            sp.mate = mate;
+            sp.add_mode(SearchMode::Mate);
+        }
+        sp.ponder = go.ponder;
+        sp.finish_limits(game_time);
+
+        UciReport::Go(sp, go.searchmoves)
+    }
+
+    fn best_move(
+        best_move: &Move,
+        ponder_move: Option<Move>,
+        board: &Arc<Mutex<Board>>,
+        out: &Transport,
+    ) {
+        let chess960 = Self::chess960(board);
+        let message = JsonMessage::BestMove {
+            best_move: best_move.as_notation(chess960),
+            ponder: ponder_move.map(|p| p.as_notation(chess960)),
+        };
+        Self::send_message(&message, out);
+    }
+
+    // In Chess960 mode, castling is written as the king taking its rook.
+    fn chess960(board: &Arc<Mutex<Board>>) -> bool {
+        board.lock().expect("Error locking board").chess960
+    }
+
+    // Each message is written as one line.
+    fn send_message(message: &JsonMessage, out: &Transport) {
+        match serde_json::to_string(message) {
+            Ok(json) => out.write_line(&json),
+            Err(e) => out.write_line(&format!("{{\"type\":\"error\",\"message\":\"{e}\"}}")),
+        }
+    }
+}
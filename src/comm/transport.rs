@@ -0,0 +1,123 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Direction markers in the transcript.
+const LOG_IN: &str = "<<"; // Received by the engine.
+const LOG_OUT: &str = ">>"; // Sent by the engine.
+
+// The connection a Comm module talks over: the console, or a client that
+// connected to the engine's TCP port. Commands are read one line at a
+// time by the report thread, and the control thread writes its output in
+// whole lines. Both threads get their own clone of the transport.
+//
+// Because all traffic passes through here, this is also where a transcript
+// of the session is written, if a log file is set.
+#[derive(Clone)]
+pub struct Transport {
+    input: Arc<Mutex<Box<dyn BufRead + Send>>>,
+    output: Arc<Mutex<Box<dyn Write + Send>>>,
+    log: Arc<Mutex<Option<File>>>,
+}
+
+impl Transport {
+    fn new(input: impl BufRead + Send + 'static, output: impl Write + Send + 'static) -> Self {
+        Self {
+            input: Arc::new(Mutex::new(Box::new(input))),
+            output: Arc::new(Mutex::new(Box::new(output))),
+            log: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Talk through stdin and stdout.
+    pub fn console() -> Self {
+        Self::new(BufReader::new(io::stdin()), io::stdout())
+    }
+
+    // Talk to a client connected over TCP.
+    pub fn tcp(stream: TcpStream) -> io::Result<Self> {
+        let input = BufReader::new(stream.try_clone()?);
+        Ok(Self::new(input, stream))
+    }
+
+    // Read the next line into 'line'. Returns the number of bytes read,
+    // which is 0 when the other side has gone away.
+    pub fn read_line(&self, line: &mut String) -> usize {
+        let mut input = self.input.lock().expect("Error locking input");
+        let bytes = input.read_line(line).unwrap_or(0);
+        if bytes > 0 {
+            self.log(LOG_IN, line.trim_end());
+        }
+        bytes
+    }
+
+    // Write one line. If the other side has gone away, the line is lost;
+    // the report thread will see the connection close and quit the engine.
+    pub fn write_line(&self, line: &str) {
+        let mut output = self.output.lock().expect("Error locking output");
+        let _ = writeln!(output, "{line}").and_then(|_| output.flush());
+        self.log(LOG_OUT, line);
+    }
+
+    // Write a transcript of everything read and written to the given file.
+    // New lines are appended, so several sessions can share one file. An
+    // empty path stops the transcript.
+    pub fn log_to(&self, path: &str) -> io::Result<()> {
+        let mut log = self.log.lock().expect("Error locking log");
+        *log = None;
+
+        if !path.is_empty() {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{} -- transcript started", Self::time_of_day())?;
+            *log = Some(file);
+        }
+
+        Ok(())
+    }
+
+    // Add a line to the transcript, with the time (UTC) and the direction
+    // in which it went. Output of more than one line is split up, so each
+    // line in the transcript has a time and a marker.
+    fn log(&self, direction: &str, text: &str) {
+        let mut log = self.log.lock().expect("Error locking log");
+        if let Some(file) = log.as_mut() {
+            let time = Self::time_of_day();
+            for line in text.lines() {
+                let _ = writeln!(file, "{time} {direction} {line}");
+            }
+        }
+    }
+
+    // Current time as hh:mm:ss.mmm (UTC).
+    fn time_of_day() -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let secs = now.as_secs() % 86_400;
+        let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+        format!("{h:02}:{m:02}:{s:02}.{:03}", now.subsec_millis())
+    }
+}
+
+// Accept connections on the given local port. Each connection gets its
+// own engine session, running in its own thread.
+pub fn serve(port: u16, session: fn(Transport)) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    println!("Listening on {}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        match stream.and_then(Transport::tcp) {
+            Ok(transport) => {
+                thread::spawn(move || session(transport));
+            }
+            Err(e) => println!("Connection failed: {e}"),
+        }
+    }
+
+    Ok(())
+}
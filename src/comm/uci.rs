@@ -1,5 +1,4 @@
 use std::{
-    io::{self},
     sync::{Arc, Mutex},
     thread::{self, JoinHandle},
 };
@@ -9,36 +8,35 @@ use crossbeam_channel::Sender;
 use crate::{
     board::Board,
     defs::{About, Sides, FEN_START_POSITION},
-    engine::defs::Information,
+    engine::defs::{EngineOption, EngineOptionName, Information, UiElement},
     evaluation::{evaluate_position, material::count},
     extra::print,
     movegen::defs::Move,
     search::{
         defs::{
-            GameTime, PerftSummary, SearchCurrentMove, SearchStats, SearchSummary, CHECKMATE,
-            CHECKMATE_THRESHOLD, INF,
+            Bound, GameTime, PerftSummary, SearchCurrentMove, SearchMode, SearchParams,
+            SearchStats, SearchSummary, BENCH_DEPTH, INF,
         },
         Search,
     },
 };
 
-use super::{CommControl, CommReport, IComm};
+use super::{transport::Transport, CommControl, CommReport, IComm};
 
 // Enum for possible uci commands
 #[derive(PartialEq, Clone, Debug)]
 pub enum UciReport {
     // Uci commands
     Uci,
+    Debug(bool), // Send extra diagnostics or not.
     UciNewGame,
     IsReady,
-    // SetOption(EngineOptionName),
+    SetOption(EngineOptionName),
     Position(String, Vec<String>),
-    GoInfinite,
-    GoDepth(i8),
+    Go(SearchParams, Vec<String>), // Search with the limits and searchmoves given to "go".
     GoPerft(i8),
-    GoMoveTime(u128),
-    GoNodes(usize),
-    GoGameTime(GameTime),
+    Bench(i8), // Search the bench positions to this depth.
+    PonderHit,
     Stop,
     Quit,
 
@@ -54,6 +52,7 @@ pub enum UciReport {
 }
 
 pub struct Uci {
+    transport: Transport,                   // Connection to the GUI.
     control_handle: Option<JoinHandle<()>>, // Control handle is
     report_handle: Option<JoinHandle<()>>,  // Report handle is
     control_tx: Option<Sender<CommControl>>, // CommControl is al possible responses engine can
@@ -61,11 +60,12 @@ pub struct Uci {
 }
 
 impl Uci {
-    pub fn new() -> Self {
+    pub fn new(transport: Transport) -> Self {
         Self {
             control_handle: None,
             report_handle: None,
             control_tx: None,
+            transport,
         }
     }
 }
@@ -74,10 +74,10 @@ impl IComm for Uci {
         &mut self,
         info_sender: Sender<Information>,
         board: Arc<Mutex<Board>>,
-        // options: Arc<Vec<EngineOption>>,
+        options: Arc<Vec<EngineOption>>,
     ) {
         self.report_thread(info_sender);
-        self.control_thread(board);
+        self.control_thread(board, options);
     }
 
     // Send messages to the control thread
@@ -111,18 +111,18 @@ impl Uci {
         // Create thread-local variables
         let mut t_incoming_data = String::from("");
         let t_info_sender = info_sender; // Report sender
+        let t_transport = self.transport.clone();
 
         // Not sure why to add move but the compiler told me
         let report_thread = thread::spawn(move || {
             let mut quit = false;
 
             while !quit {
-                // read data from std:in
-                io::stdin()
-                    .read_line(&mut t_incoming_data)
-                    .expect("error reading input");
-
-                let response_report = Self::create_report(&t_incoming_data);
+                // Read the next command. Quit if the GUI went away.
+                let response_report = match t_transport.read_line(&mut t_incoming_data) {
+                    0 => CommReport::Uci(UciReport::Quit),
+                    _ => Self::create_report(&t_incoming_data, &t_transport),
+                };
 
                 if response_report.is_valid() {
                     // send to engine receiving thread
@@ -143,11 +143,13 @@ impl Uci {
 
 // implement control thread
 impl Uci {
-    pub fn control_thread(&mut self, board: Arc<Mutex<Board>>) {
+    pub fn control_thread(&mut self, board: Arc<Mutex<Board>>, options: Arc<Vec<EngineOption>>) {
         // Create an incoming channel for the control thread.
         let (control_tx, control_rx) = crossbeam_channel::unbounded::<CommControl>();
 
         let t_board = Arc::clone(&board);
+        let t_options = Arc::clone(&options);
+        let t_out = self.transport.clone();
         // not sure why to add move
         let control_thread = thread::spawn(move || {
             let mut quit = false;
@@ -156,24 +158,38 @@ impl Uci {
 
                 match control {
                     CommControl::Identify => {
-                        println!("id name {} {}", About::ENGINE, About::VERSION);
-                        println!("id author {}", About::AUTHOR);
-                        println!("uciok");
+                        t_out.write_line(&format!("id name {} {}", About::ENGINE, About::VERSION));
+                        t_out.write_line(&format!("id author {}", About::AUTHOR));
+                        Self::options(&t_options, &t_out);
+                        t_out.write_line("uciok");
                     }
-                    CommControl::Ready => println!("readyok"),
+                    CommControl::Ready => t_out.write_line("readyok"),
                     CommControl::Quit => quit = true,
-                    CommControl::SearchSummary(summary) => Self::search_summary(&summary, &t_board),
-                    CommControl::SearchStats(stats) => Self::search_stats(&stats),
-                    CommControl::SearchCurrMove(current) => Self::search_current_move(&current),
-                    CommControl::InfoString(info) => Self::info_string(&info),
-                    CommControl::BestMove(best_move) => Self::find_best_move(&best_move),
-                    CommControl::PerftScore(perftsum) => Self::perft_summary(&perftsum),
+                    CommControl::SearchSummary(summary) => {
+                        Self::search_summary(&summary, &t_board, &t_out)
+                    }
+                    CommControl::SearchStats(stats) => Self::search_stats(&stats, &t_out),
+                    CommControl::SearchCurrMove(current) => {
+                        Self::search_current_move(&current, &t_board, &t_out)
+                    }
+                    CommControl::InfoString(info) => Self::info_string(&info, &t_out),
+                    CommControl::BestMove(best_move, ponder_move) => {
+                        Self::find_best_move(&best_move, ponder_move, &t_board, &t_out)
+                    }
+                    CommControl::PerftScore(perftsum) => Self::perft_summary(&perftsum, &t_out),
                     CommControl::SolvePuzzles => (),
-                    CommControl::PrintBoard => Self::print_board(&t_board),
+                    CommControl::PrintBoard => Self::print_board(&t_board, &t_out),
                     CommControl::PrintHistory => (),
                     CommControl::PrintHelp => (),
+                    CommControl::IllegalMove(m) => {
+                        Self::info_string(&format!("{m}: illegal move"), &t_out)
+                    }
+                    CommControl::Error(e) => Self::info_string(&format!("error: {e}"), &t_out),
 
-                    CommControl::Update => (),
+                    CommControl::Pong(_)
+                    | CommControl::Hint(_)
+                    | CommControl::GameResult(_)
+                    | CommControl::Update => (),
                 }
             }
         });
@@ -186,23 +202,42 @@ impl Uci {
 // Some private function for Uci
 impl Uci {
     // Create a rapport message so the engine understands what to do
-    fn create_report(message: &str) -> CommReport {
+    fn create_report(message: &str, transport: &Transport) -> CommReport {
         let clean_message = message.trim_end().to_string();
         match clean_message {
             cmd if cmd == "uci" => CommReport::Uci(UciReport::Uci),
+            cmd if cmd == "debug on" => CommReport::Uci(UciReport::Debug(true)),
+            cmd if cmd == "debug off" => CommReport::Uci(UciReport::Debug(false)),
             cmd if cmd == "ucinewgame" => CommReport::Uci(UciReport::UciNewGame),
             cmd if cmd == "isready" => CommReport::Uci(UciReport::IsReady),
             cmd if cmd == "stop" => CommReport::Uci(UciReport::Stop),
+            cmd if cmd == "ponderhit" => CommReport::Uci(UciReport::PonderHit),
             cmd if cmd == "quit" || cmd == "exit" => CommReport::Uci(UciReport::Quit),
             cmd if cmd.starts_with("position") => Self::parse_position(&cmd),
-            // cmd if cmd.starts_with("setoption") => Self::parse_options(&cmd),
+            cmd if cmd.starts_with("setoption") => Self::parse_setoption(&cmd),
             cmd if cmd.starts_with("go") => Self::parse_go(&cmd),
             cmd if cmd == "d" => CommReport::Uci(UciReport::Board),
-            cmd if cmd == "puzzles" => Self::solve_puzzles(),
+            cmd if cmd.starts_with("bench") => Self::parse_bench(&cmd),
+            cmd if cmd == "puzzles" => Self::solve_puzzles(transport),
             _ => CommReport::Uci(UciReport::Unknown),
         }
     }
 
+    // "bench" searches the bench positions to the default depth, and
+    // "bench <depth>" to the given depth.
+    fn parse_bench(command: &str) -> CommReport {
+        let mut parts = command.split_whitespace();
+        if parts.next() != Some("bench") {
+            return CommReport::Uci(UciReport::Unknown);
+        }
+
+        match parts.next().map(str::parse::<i8>) {
+            None => CommReport::Uci(UciReport::Bench(BENCH_DEPTH)),
+            Some(Ok(depth)) if depth > 0 => CommReport::Uci(UciReport::Bench(depth)),
+            Some(_) => CommReport::Uci(UciReport::Unknown),
+        }
+    }
+
     fn parse_position(command: &str) -> CommReport {
         enum Tokens {
             Nothing,
@@ -253,16 +288,23 @@ impl Uci {
             BInc,
             MovesToGo,
             Perft,
+            SearchMoves,
+            Mate,
         }
 
         let go_parts: Vec<String> = command.split_whitespace().map(|s| s.to_string()).collect();
-        let mut report = CommReport::Uci(UciReport::Unknown);
         let mut token = Tokens::Nothing;
+        let mut sp = SearchParams::new();
         let mut game_time = GameTime::new(0, 0, 0, 0, None);
+        let mut search_moves: Vec<String> = Vec::new();
+
+        // All parameters are collected, so several limits can be active at
+        // the same time. (For example "go wtime 60000 btime 60000 depth 12".)
         for part in go_parts {
             match part {
-                t if t == "go" => report = CommReport::Uci(UciReport::GoInfinite),
-                t if t == "infinite" => break, // Already Infinite; nothing more to do.
+                t if t == "go" => (), // Skip. We know we're parsing "go".
+                t if t == "infinite" => sp.add_mode(SearchMode::Infinite),
+                t if t == "ponder" => sp.ponder = true,
                 t if t == "depth" => token = Tokens::Depth,
                 t if t == "perft" => token = Tokens::Perft,
                 t if t == "movetime" => token = Tokens::MoveTime,
@@ -272,27 +314,25 @@ impl Uci {
                 t if t == "winc" => token = Tokens::WInc,
                 t if t == "binc" => token = Tokens::BInc,
                 t if t == "movestogo" => token = Tokens::MovesToGo,
+                t if t == "searchmoves" => token = Tokens::SearchMoves,
+                t if t == "mate" => token = Tokens::Mate,
                 _ => match token {
                     Tokens::Nothing => (),
                     Tokens::Depth => {
-                        let depth = part.parse::<i8>().unwrap_or(1);
-                        report = CommReport::Uci(UciReport::GoDepth(depth));
-                        break; // break for-loop: nothing more to do.
+                        sp.depth = part.parse::<i8>().unwrap_or(1);
+                        sp.add_mode(SearchMode::Depth);
                     }
                     Tokens::Perft => {
                         let perft = part.parse::<i8>().unwrap_or(1);
-                        report = CommReport::Uci(UciReport::GoPerft(perft));
-                        break; // break for-loop: nothing more to do.
+                        return CommReport::Uci(UciReport::GoPerft(perft));
                     }
                     Tokens::MoveTime => {
-                        let milliseconds = part.parse::<u128>().unwrap_or(1000);
-                        report = CommReport::Uci(UciReport::GoMoveTime(milliseconds));
-                        break; // break for-loop: nothing more to do.
+                        sp.move_time = part.parse::<u128>().unwrap_or(1000);
+                        sp.add_mode(SearchMode::MoveTime);
                     }
                     Tokens::Nodes => {
-                        let nodes = part.parse::<usize>().unwrap_or(1);
-                        report = CommReport::Uci(UciReport::GoNodes(nodes));
-                        break; // break for-loop: nothing more to do.
+                        sp.nodes = part.parse::<usize>().unwrap_or(1);
+                        sp.add_mode(SearchMode::Nodes);
                     }
                     Tokens::WTime => game_time.white_time = part.parse::<u128>().unwrap_or(0),
                     Tokens::BTime => game_time.black_time = part.parse::<u128>().unwrap_or(0),
@@ -305,50 +345,109 @@ impl Uci {
                             None
                         }
                     }
+                    Tokens::SearchMoves => search_moves.push(part),
+                    Tokens::Mate => {
+                        sp.mate = part.parse::<u8>().unwrap_or(1);
+                        sp.add_mode(SearchMode::Mate);
+                    }
                 },
             }
         }
 
-        // if mode is still GoInfinite we must switch to gametime mode
-        let is_default_mode = report == CommReport::Uci(UciReport::GoInfinite);
-        let has_time = game_time.white_time > 0 || game_time.black_time > 0;
-        let has_inc = game_time.white_time_incr > 0 || game_time.black_time_incr > 0;
-        let is_game_time = has_time || has_inc;
-        if is_default_mode && is_game_time {
-            report = CommReport::Uci(UciReport::GoGameTime(game_time));
-        }
+        sp.finish_limits(game_time);
 
-        report
+        CommReport::Uci(UciReport::Go(sp, search_moves))
     }
 
-    // fn parse_options(command: &str) -> CommReport {
-    //     CommReport::Uci(UciReport::SetOption())
-    // }
+    fn parse_setoption(command: &str) -> CommReport {
+        enum Tokens {
+            Nothing,
+            Name,
+            Value,
+        }
 
-    fn search_summary(summary: &SearchSummary, board: &Arc<Mutex<Board>>) {
-        // Check for checkmate
-        let score = if summary.cp == -INF {
-            format!("draw")
-        } else if (summary.cp.abs() >= CHECKMATE_THRESHOLD) && (summary.cp.abs() <= CHECKMATE) {
-            // number of plays left to mate
-            let plays = CHECKMATE - summary.cp.abs();
+        let parts: Vec<String> = command.split_whitespace().map(|s| s.to_string()).collect();
+        let mut token = Tokens::Nothing;
+        let mut name = String::from(""); // Option name can contain spaces.
+        let mut value = String::from(""); // So can the value (BookFile).
+
+        for part in parts {
+            match part {
+                t if t == "setoption" => (), // Skip. We know we're parsing "setoption".
+                t if t == "name" => token = Tokens::Name,
+                t if t == "value" => token = Tokens::Value,
+                _ => match token {
+                    Tokens::Name => name = format!("{name} {part}"),
+                    Tokens::Value => value = format!("{value} {part}"),
+                    Tokens::Nothing => (),
+                },
+            }
+        }
+
+        let option = EngineOptionName::new(name.trim(), value.trim().to_string());
 
-            // Check if the number of ply's is odd
-            let is_odd = plays % 2 == 1;
+        CommReport::Uci(UciReport::SetOption(option))
+    }
 
-            // Calculate number of moves to mate
-            let moves = if is_odd { (plays + 1) / 2 } else { plays / 2 };
+    // Advertise the options the engine supports, as part of the reply
+    // to the "uci" command.
+    fn options(options: &Arc<Vec<EngineOption>>, out: &Transport) {
+        for o in options.iter() {
+            let ui_element = match o.ui_element {
+                UiElement::Spin => "spin",
+                UiElement::Check => "check",
+                UiElement::Button => "button",
+                UiElement::String => "string",
+            };
+
+            let mut option = format!("option name {} type {}", o.name, ui_element);
+            if let Some(default) = &o.default {
+                // An empty string value is sent as "<empty>".
+                let default = if default.is_empty() {
+                    "<empty>"
+                } else {
+                    default
+                };
+                option = format!("{option} default {default}");
+            }
+            if let Some(min) = &o.min {
+                option = format!("{option} min {min}");
+            }
+            if let Some(max) = &o.max {
+                option = format!("{option} max {max}");
+            }
 
-            // If the engine is being mated itself, flip the score.
-            let flip = if summary.cp < 0 { -1 } else { 1 };
+            out.write_line(&option);
+        }
+    }
 
-            // Report the mate
-            format!("mate {}", moves * flip)
+    fn search_summary(summary: &SearchSummary, board: &Arc<Mutex<Board>>, out: &Transport) {
+        // Check for checkmate
+        let score = if summary.cp == -INF {
+            format!("draw")
+        } else if summary.mate != 0 {
+            // Report the mate (negative if the engine is getting mated).
+            format!("mate {}", summary.mate)
         } else {
             // Report the normal score if there's no mate detected.
             format!("cp {}", summary.cp)
         };
 
+        // Mark the score as a bound if the aspiration window failed.
+        let score = match summary.bound {
+            Bound::Exact => score,
+            Bound::Lower => format!("{score} lowerbound"),
+            Bound::Upper => format!("{score} upperbound"),
+        };
+
+        // Add the win/draw/loss chances if the GUI asked for them.
+        let score = match summary.wdl {
+            Some(wdl) if summary.cp != -INF => {
+                format!("{score} wdl {} {} {}", wdl.win, wdl.draw, wdl.loss)
+            }
+            _ => score,
+        };
+
         // Report depth and seldepth (if available).
         let depth = if summary.seldepth > 0 {
             format!("depth {} seldepth {}", summary.depth, summary.seldepth)
@@ -356,16 +455,16 @@ impl Uci {
             format!("depth {}", summary.depth)
         };
 
-        // // Only display hash full if not 0
-        // let hash_full = if summary.hash_full > 0 {
-        //     format!(" hashfull {} ", summary.hash_full)
-        // } else {
-        //     String::from(" ")
-        // };
-
-        let pv = summary.pv_as_string();
+        // Only display hash full if not 0
+        let hash_full = if summary.hash_full > 0 {
+            format!(" hashfull {}", summary.hash_full)
+        } else {
+            String::from("")
+        };
 
         let board_lock = board.lock().expect("Error locking board");
+        let pv = summary.pv_as_string(board_lock.chess960);
+
         let eval = evaluate_position(&board_lock);
         let w_psqt = &board_lock.gamestate.psqt[Sides::WHITE];
         let b_psqt = &board_lock.gamestate.psqt[Sides::BLACK];
@@ -373,43 +472,63 @@ impl Uci {
         let (w_material, b_material) = count(&board_lock);
 
         let info = format!(
-            "info score {} {} time {} nodes {} nps {} pv {} eval {} w_psqt {} b_psqt {} w_material {} b_material {}",
-            score, depth, summary.time, summary.nodes, summary.nps, pv, eval, w_psqt, b_psqt, w_material, b_material
+            "info multipv {} score {} {} time {} nodes {} nps {}{} pv {} eval {} w_psqt {} b_psqt {} w_material {} b_material {}",
+            summary.multipv, score, depth, summary.time, summary.nodes, summary.nps, hash_full, pv, eval, w_psqt, b_psqt, w_material, b_material
         );
 
-        println!("{info}");
+        out.write_line(&info);
     }
 
-    fn search_stats(stats: &SearchStats) {
+    fn search_stats(stats: &SearchStats, out: &Transport) {
         let hash_full = if stats.hash_full > 0 {
             format!(" hashfull {}", stats.hash_full)
         } else {
             String::from("")
         };
 
-        println!(
+        out.write_line(&format!(
             "info time {} nodes {} nps {}{}",
             stats.time, stats.nodes, stats.nps, hash_full
-        );
+        ));
     }
 
-    fn search_current_move(current: &SearchCurrentMove) {
-        format!(
+    fn search_current_move(
+        current: &SearchCurrentMove,
+        board: &Arc<Mutex<Board>>,
+        out: &Transport,
+    ) {
+        out.write_line(&format!(
             "info currmove {} currmovenumber {}",
-            current.curr_move.as_string(),
+            current.curr_move.as_notation(Self::chess960(board)),
             current.curr_move_number
-        );
+        ));
+    }
+
+    fn info_string(msg: &str, out: &Transport) {
+        out.write_line(&format!("info string {msg}"));
     }
 
-    fn info_string(msg: &str) {
-        println!("info string {msg}");
+    fn find_best_move(
+        bestmove: &Move,
+        ponder_move: Option<Move>,
+        board: &Arc<Mutex<Board>>,
+        out: &Transport,
+    ) {
+        let chess960 = Self::chess960(board);
+        let bestmove = bestmove.as_notation(chess960);
+        let line = match ponder_move {
+            Some(p) => format!("bestmove {} ponder {}", bestmove, p.as_notation(chess960)),
+            None => format!("bestmove {}", bestmove),
+        };
+        out.write_line(&line);
     }
 
-    fn find_best_move(bestmove: &Move) {
-        println!("bestmove {}", bestmove.as_string());
+    // In Chess960 mode, castling is written as the king taking its rook.
+    fn chess960(board: &Arc<Mutex<Board>>) -> bool {
+        board.lock().expect("Error locking board").chess960
     }
 
-    fn perft_summary(summary: &PerftSummary) {
+    fn perft_summary(summary: &PerftSummary, out: &Transport) {
         let mut sorted_vec: Vec<_> = summary.moves.clone().into_iter().collect();
 
         // Sort the vector based on the keys
@@ -417,50 +536,47 @@ impl Uci {
 
         // Iterate over the sorted vector
         for (key, value) in sorted_vec {
-            println!("{}: {}", key, value);
+            out.write_line(&format!("{}: {}", key, value));
         }
 
-        println!(
+        out.write_line(&format!(
             "\nDepth: {}\nnodes: {}\nNodes per second: {}\nTime: {:?} milliseconds\n",
             summary.depth,
             summary.nodes,
             Search::nodes_per_sec(summary.nodes as usize, summary.time.as_millis()),
             summary.time.as_millis()
-        );
+        ));
 
-        summary.move_stats.log();
+        out.write_line(&summary.move_stats.to_string());
     }
 
-    fn print_board(board: &Arc<Mutex<Board>>) {
-        print::print_position(&board.lock().expect("Error locking board"), false, None);
+    fn print_board(board: &Arc<Mutex<Board>>, out: &Transport) {
+        let board = board.lock().expect("Error locking board");
+        out.write_line(print::position_as_string(&board, false, None).trim_end());
     }
 
-    fn solve_puzzles() ->  CommReport {
+    fn solve_puzzles(transport: &Transport) -> CommReport {
         let mut moves: Vec<String> = Vec::new();
         let mut input = String::new();
-        println!("Enter 'm' for manual FEN input, 'd' for database puzzles, or select a number (3-3) for preloaded puzzles:");
+        transport.write_line("Enter 'm' for manual FEN input, 'd' for database puzzles, or select a number (3-3) for preloaded puzzles:");
 
-        // Read user input from stdin
-        io::stdin().read_line(&mut input).expect("Failed to read input");
+        // Read user input
+        transport.read_line(&mut input);
         match input.trim() {
-            "m" =>  {
+            "m" => {
                 let mut manual_fen = String::new();
-                println!("Please enter a valid FEN string:");
-                io::stdin()
-                    .read_line(&mut manual_fen)
-                    .expect("Failed to read input");
+                transport.write_line("Please enter a valid FEN string:");
+                transport.read_line(&mut manual_fen);
                 CommReport::Uci(UciReport::Position(manual_fen.trim().to_string(), moves));
-                CommReport::Uci(UciReport::GoInfinite)
+                let mut sp = SearchParams::new();
+                sp.add_mode(SearchMode::Infinite);
+                CommReport::Uci(UciReport::Go(sp, Vec::new()))
             }
-            "d" => {
-                CommReport::Uci(UciReport::Puzzle)
+            "d" => CommReport::Uci(UciReport::Puzzle),
+            _ => {
+                transport.write_line("Invalid input. Defaulting to puzzle testcases");
+                CommReport::Uci(UciReport::Unknown)
             }
-            _ => { 
-                    println!("Invalid input. Defaulting to puzzle testcases");
-                    CommReport::Uci(UciReport::Unknown)
-                }
         }
     }
-
-
 }
@@ -0,0 +1,404 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{
+    board::Board,
+    defs::About,
+    engine::defs::{EngineOption, Information},
+    extra::print,
+    movegen::defs::Move,
+    search::defs::{Bound, PerftSummary, SearchStats, SearchSummary, INF},
+};
+
+use super::{transport::Transport, CommControl, CommReport, IComm};
+
+// XBoard reports mate scores as 100000 + moves to mate.
+const XBOARD_MATE: i32 = 100_000;
+
+// Time control as sent by the "level" and "st" commands.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum TimeControl {
+    // Conventional or incremental clock: moves per session, base time
+    // (msecs) and increment (msecs) per move.
+    Level(usize, u128, u128),
+    SecondsPerMove(u128), // Fixed time per move (msecs).
+}
+
+// Enum for possible xboard commands
+#[derive(PartialEq, Clone, Debug)]
+pub enum XBoardReport {
+    // XBoard commands
+    XBoard,
+    ProtoVer(u8),
+    New,
+    Force,
+    Go,
+    PlayOther,
+    SetBoard(String),
+    UserMove(String),
+    TimeControl(TimeControl),
+    Sd(i8),
+    Time(u128),
+    OTim(u128),
+    Analyze,
+    Exit,
+    Undo,
+    Remove,
+    Result(String),
+    Post,
+    NoPost,
+    Ping(u32),
+    MoveNow,
+    Hint,
+    Quit,
+
+    // Custom commands
+    Board,
+
+    // Empty or unknown command.
+    Unknown,
+}
+
+pub struct XBoard {
+    control_handle: Option<JoinHandle<()>>,
+    report_handle: Option<JoinHandle<()>>,
+    control_tx: Option<Sender<CommControl>>,
+    transport: Transport, // Connection to the GUI.
+}
+
+impl XBoard {
+    pub fn new(transport: Transport) -> Self {
+        Self {
+            control_handle: None,
+            report_handle: None,
+            control_tx: None,
+            transport,
+        }
+    }
+}
+
+impl IComm for XBoard {
+    // XBoard has its own option mechanism; the UCI options are not used.
+    fn init(
+        &mut self,
+        info_sender: Sender<Information>,
+        board: Arc<Mutex<Board>>,
+        _options: Arc<Vec<EngineOption>>,
+    ) {
+        self.report_thread(info_sender);
+        self.control_thread(board);
+    }
+
+    // Send messages to the control thread
+    fn send(&self, msg: CommControl) {
+        if let Some(tx) = &self.control_tx {
+            tx.send(msg).expect("Failed sending message {msg}");
+        }
+    }
+
+    // After the engine sends 'quit' to the control thread, it will call
+    // wait_for_shutdown() and then wait here until shutdown is completed.
+    fn wait_for_shutdown(&mut self) {
+        if let Some(h) = self.report_handle.take() {
+            h.join().expect("error stopping thread");
+        }
+
+        if let Some(h) = self.control_handle.take() {
+            h.join().expect("error stopping thread");
+        }
+    }
+
+    // This function just returns the name of the communication protocol.
+    fn get_protocol_name(&self) -> &'static str {
+        "xboard"
+    }
+}
+
+// Implement report thread
+impl XBoard {
+    pub fn report_thread(&mut self, info_sender: Sender<Information>) {
+        // Create thread-local variables
+        let mut t_incoming_data = String::from("");
+        let t_info_sender = info_sender; // Report sender
+        let t_transport = self.transport.clone();
+
+        let report_thread = thread::spawn(move || {
+            let mut quit = false;
+
+            while !quit {
+                // Read the next command. Quit if the GUI went away.
+                let response_report = match t_transport.read_line(&mut t_incoming_data) {
+                    0 => CommReport::XBoard(XBoardReport::Quit),
+                    _ => Self::create_report(&t_incoming_data),
+                };
+
+                if response_report.is_valid() {
+                    // send to engine receiving thread
+                    t_info_sender
+                        .send(Information::Comm(response_report.clone()))
+                        .expect("Error sending message");
+
+                    quit = response_report == CommReport::XBoard(XBoardReport::Quit);
+                }
+
+                // clear data for next incoming message
+                t_incoming_data = String::from("");
+            }
+        });
+
+        self.report_handle = Some(report_thread);
+    }
+}
+
+// Implement control thread
+impl XBoard {
+    pub fn control_thread(&mut self, board: Arc<Mutex<Board>>) {
+        // Create an incoming channel for the control thread.
+        let (control_tx, control_rx) = crossbeam_channel::unbounded::<CommControl>();
+
+        let t_board = Arc::clone(&board);
+        let t_out = self.transport.clone();
+        let control_thread = thread::spawn(move || {
+            let mut quit = false;
+            while !quit {
+                let control = control_rx.recv().expect("error receiving message");
+
+                match control {
+                    CommControl::Identify => Self::features(&t_out),
+                    CommControl::Pong(n) => t_out.write_line(&format!("pong {n}")),
+                    CommControl::Quit => quit = true,
+                    CommControl::SearchSummary(summary) => Self::search_summary(&summary, &t_out),
+                    CommControl::SearchStats(stats) => Self::search_stats(&stats, &t_out),
+                    CommControl::InfoString(info) => Self::info_string(&info, &t_out),
+                    CommControl::BestMove(best_move, _) => Self::best_move(&best_move, &t_out),
+                    CommControl::IllegalMove(m) => t_out.write_line(&format!("Illegal move: {m}")),
+                    CommControl::Error(e) => t_out.write_line(&format!("tellusererror {e}")),
+                    CommControl::Hint(m) => t_out.write_line(&format!("Hint: {}", m.as_string())),
+                    CommControl::GameResult(result) => t_out.write_line(&result),
+                    CommControl::PerftScore(perftsum) => Self::perft_summary(&perftsum, &t_out),
+                    CommControl::PrintBoard => Self::print_board(&t_board, &t_out),
+
+                    // Not used by XBoard.
+                    CommControl::Ready
+                    | CommControl::SearchCurrMove(_)
+                    | CommControl::SolvePuzzles
+                    | CommControl::PrintHistory
+                    | CommControl::PrintHelp
+                    | CommControl::Update => (),
+                }
+            }
+        });
+
+        self.control_handle = Some(control_thread);
+        self.control_tx = Some(control_tx);
+    }
+}
+
+// Some private functions for XBoard
+impl XBoard {
+    // Create a report message so the engine understands what to do
+    fn create_report(message: &str) -> CommReport {
+        let clean_message = message.trim().to_string();
+        match clean_message {
+            cmd if cmd == "xboard" => CommReport::XBoard(XBoardReport::XBoard),
+            cmd if cmd == "new" => CommReport::XBoard(XBoardReport::New),
+            cmd if cmd == "force" => CommReport::XBoard(XBoardReport::Force),
+            cmd if cmd == "go" => CommReport::XBoard(XBoardReport::Go),
+            cmd if cmd == "playother" => CommReport::XBoard(XBoardReport::PlayOther),
+            cmd if cmd == "analyze" => CommReport::XBoard(XBoardReport::Analyze),
+            cmd if cmd == "exit" => CommReport::XBoard(XBoardReport::Exit),
+            cmd if cmd == "undo" => CommReport::XBoard(XBoardReport::Undo),
+            cmd if cmd == "remove" => CommReport::XBoard(XBoardReport::Remove),
+            cmd if cmd == "post" => CommReport::XBoard(XBoardReport::Post),
+            cmd if cmd == "nopost" => CommReport::XBoard(XBoardReport::NoPost),
+            cmd if cmd == "?" => CommReport::XBoard(XBoardReport::MoveNow),
+            cmd if cmd == "hint" => CommReport::XBoard(XBoardReport::Hint),
+            cmd if cmd == "quit" => CommReport::XBoard(XBoardReport::Quit),
+            cmd if cmd == "d" => CommReport::XBoard(XBoardReport::Board),
+            cmd if cmd.starts_with("protover") => Self::parse_protover(&cmd),
+            cmd if cmd.starts_with("setboard") => Self::parse_setboard(&cmd),
+            cmd if cmd.starts_with("usermove") => Self::parse_usermove(&cmd),
+            cmd if cmd.starts_with("level") => Self::parse_level(&cmd),
+            cmd if cmd.starts_with("st ") => Self::parse_st(&cmd),
+            cmd if cmd.starts_with("sd ") => Self::parse_sd(&cmd),
+            cmd if cmd.starts_with("time") => Self::parse_time(&cmd),
+            cmd if cmd.starts_with("otim") => Self::parse_otim(&cmd),
+            cmd if cmd.starts_with("ping") => Self::parse_ping(&cmd),
+            cmd if cmd.starts_with("result") => Self::parse_result(&cmd),
+            _ => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    // Returns everything after the command itself.
+    fn arguments(command: &str) -> &str {
+        match command.split_once(' ') {
+            Some((_, args)) => args.trim(),
+            None => "",
+        }
+    }
+
+    fn parse_protover(command: &str) -> CommReport {
+        match Self::arguments(command).parse::<u8>() {
+            Ok(v) => CommReport::XBoard(XBoardReport::ProtoVer(v)),
+            Err(_) => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    fn parse_setboard(command: &str) -> CommReport {
+        let fen = Self::arguments(command).to_string();
+        CommReport::XBoard(XBoardReport::SetBoard(fen))
+    }
+
+    fn parse_usermove(command: &str) -> CommReport {
+        let m = Self::arguments(command).to_string();
+        CommReport::XBoard(XBoardReport::UserMove(m))
+    }
+
+    // Parse "level MPS BASE INC". BASE is in minutes, or "minutes:seconds".
+    // INC is in (possibly fractional) seconds.
+    fn parse_level(command: &str) -> CommReport {
+        let parts: Vec<&str> = Self::arguments(command).split_whitespace().collect();
+        if parts.len() != 3 {
+            return CommReport::XBoard(XBoardReport::Unknown);
+        }
+
+        let mps = parts[0].parse::<usize>().ok();
+        let base = match parts[1].split_once(':') {
+            Some((m, s)) => match (m.parse::<u128>(), s.parse::<u128>()) {
+                (Ok(m), Ok(s)) => Some((m * 60 + s) * 1000),
+                _ => None,
+            },
+            None => parts[1].parse::<u128>().ok().map(|m| m * 60 * 1000),
+        };
+        let inc = parts[2]
+            .parse::<f64>()
+            .ok()
+            .map(|s| (s * 1000f64).round() as u128);
+
+        match (mps, base, inc) {
+            (Some(mps), Some(base), Some(inc)) => {
+                let tc = TimeControl::Level(mps, base, inc);
+                CommReport::XBoard(XBoardReport::TimeControl(tc))
+            }
+            _ => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    fn parse_st(command: &str) -> CommReport {
+        match Self::arguments(command).parse::<u128>() {
+            Ok(s) => {
+                let tc = TimeControl::SecondsPerMove(s * 1000);
+                CommReport::XBoard(XBoardReport::TimeControl(tc))
+            }
+            Err(_) => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    fn parse_sd(command: &str) -> CommReport {
+        match Self::arguments(command).parse::<i8>() {
+            Ok(d) => CommReport::XBoard(XBoardReport::Sd(d)),
+            Err(_) => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    // XBoard sends times in centiseconds; the engine uses milliseconds.
+    fn parse_time(command: &str) -> CommReport {
+        match Self::arguments(command).parse::<u128>() {
+            Ok(cs) => CommReport::XBoard(XBoardReport::Time(cs * 10)),
+            Err(_) => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    fn parse_otim(command: &str) -> CommReport {
+        match Self::arguments(command).parse::<u128>() {
+            Ok(cs) => CommReport::XBoard(XBoardReport::OTim(cs * 10)),
+            Err(_) => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    fn parse_ping(command: &str) -> CommReport {
+        match Self::arguments(command).parse::<u32>() {
+            Ok(n) => CommReport::XBoard(XBoardReport::Ping(n)),
+            Err(_) => CommReport::XBoard(XBoardReport::Unknown),
+        }
+    }
+
+    fn parse_result(command: &str) -> CommReport {
+        let result = Self::arguments(command).to_string();
+        CommReport::XBoard(XBoardReport::Result(result))
+    }
+
+    fn features(out: &Transport) {
+        out.write_line("feature done=0");
+        out.write_line(&format!(
+            "feature myname=\"{} {}\" ping=1 setboard=1 usermove=1 playother=1 time=1 \
+             analyze=1 colors=0 reuse=1 sigint=0 sigterm=0 draw=0 variants=\"normal\"",
+            About::ENGINE,
+            About::VERSION
+        ));
+        out.write_line("feature done=1");
+    }
+
+    // Thinking output: "ply score time nodes pv", with the time in
+    // centiseconds.
+    fn search_summary(summary: &SearchSummary, out: &Transport) {
+        // The protocol has no way to mark a score as a bound, so the
+        // results of a failed aspiration window are not shown.
+        if summary.bound != Bound::Exact {
+            return;
+        }
+
+        let score = if summary.cp == -INF {
+            0
+        } else if summary.mate != 0 {
+            let moves = summary.mate.abs() as i32;
+            let flip = if summary.mate < 0 { -1 } else { 1 };
+
+            (XBOARD_MATE + moves) * flip
+        } else {
+            summary.cp as i32
+        };
+
+        out.write_line(&format!(
+            "{} {} {} {} {}",
+            summary.depth,
+            score,
+            summary.time / 10,
+            summary.nodes,
+            summary.pv_as_string(false)
+        ));
+    }
+
+    fn search_stats(stats: &SearchStats, out: &Transport) {
+        out.write_line(&format!(
+            "# time {} nodes {} nps {}",
+            stats.time, stats.nodes, stats.nps
+        ));
+    }
+
+    fn info_string(msg: &str, out: &Transport) {
+        out.write_line(&format!("# {msg}"));
+    }
+
+    fn best_move(best_move: &Move, out: &Transport) {
+        out.write_line(&format!("move {}", best_move.as_string()));
+    }
+
+    fn perft_summary(summary: &PerftSummary, out: &Transport) {
+        out.write_line(&format!(
+            "# perft depth {} nodes {} time {}",
+            summary.depth,
+            summary.nodes,
+            summary.time.as_millis()
+        ));
+    }
+
+    fn print_board(board: &Arc<Mutex<Board>>, out: &Transport) {
+        let board = board.lock().expect("Error locking board");
+        out.write_line(print::position_as_string(&board, false, None).trim_end());
+    }
+}
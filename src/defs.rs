@@ -1,3 +1,7 @@
+use std::fmt;
+
+use crate::board::FenError;
+
 pub type Bitboard = u64;
 pub type Piece = usize;
 pub type Side = usize;
@@ -38,6 +42,9 @@ impl Castling {
     pub const BK: u8 = 4;
     pub const BQ: u8 = 8;
     pub const ALL: u8 = 15;
+
+    // Castling rights per side, for the king and queen side.
+    pub const RIGHTS: [[u8; 2]; Sides::BOTH] = [[Self::WK, Self::WQ], [Self::BK, Self::BQ]];
 }
 
 pub const EMPTY: u64 = 0;
@@ -47,14 +54,39 @@ pub const MAX_PLY: i8 = 125;
 pub const MAX_MOVE_RULE: u8 = 100; // 50/75 move rule
 
 // Define errors
-pub type EngineRunResult = Result<(), u8>;
-// pub const ENGINE_RUN_ERRORS: [&str; 8] = [
-//     "FEN: Must have six parts",
-//     "FEN: Pieces and squares incorrect",
-//     "FEN: Color selection incorrect",
-//     "FEN: Castling permissions incorrect",
-//     "FEN: En-passant square incorrect",
-//     "FEN: Half-move clock incorrect",
-//     "FEN: Full-move number incorrect",
-//     "XBoard not yet implemented.",
-// ];
+#[derive(Clone, Debug, PartialEq)]
+pub enum EngineError {
+    Fen(FenError),       // Position that can't be set up.
+    InvalidMove(String), // Move that can't be read.
+    IllegalMove(String), // Move that can't be played in the position.
+    Io(String, String),  // File and the reason it can't be used.
+    Book(String, usize), // Book file and the line that can't be read.
+    Protocol(String),    // Command or option the engine can't accept.
+}
+
+impl EngineError {
+    pub fn io(file: &str, e: &std::io::Error) -> Self {
+        EngineError::Io(file.to_string(), e.to_string())
+    }
+}
+
+impl From<FenError> for EngineError {
+    fn from(e: FenError) -> Self {
+        EngineError::Fen(e)
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Fen(e) => write!(f, "invalid position: {e}"),
+            EngineError::InvalidMove(m) => write!(f, "invalid move: {m}"),
+            EngineError::IllegalMove(m) => write!(f, "illegal move: {m}"),
+            EngineError::Io(file, e) => write!(f, "{file}: {e}"),
+            EngineError::Book(file, line) => write!(f, "{file}:{line}: invalid book entry"),
+            EngineError::Protocol(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+pub type EngineRunResult = Result<(), EngineError>;
@@ -1,5 +1,6 @@
 pub mod comm_report;
 pub mod defs;
+pub mod epd_test;
 pub mod search_report;
 pub mod transposition;
 pub mod utils;
@@ -7,64 +8,199 @@ use std::sync::{Arc, Mutex};
 
 use crate::{
     board::Board,
-    comm::{uci::Uci, CommControl, IComm},
+    comm::{
+        console::Console, json::Json, transport::Transport, uci::Uci, xboard::XBoard,
+        CommControl, IComm,
+    },
     defs::EngineRunResult,
     extra::cmdline::Cmdline,
     movegen::MoveGenerator,
-    search::{defs::SearchControl, Search},
+    search::{defs::SearchControl, helpers::MoveBook, Search},
 };
 use crossbeam_channel::Receiver;
 use transposition::{SearchData, TT};
 
-use self::defs::{Information, Settings};
+use self::defs::{
+    EngineOption, EngineOptionDefaults, EngineOptionNames, Information, Settings, UiElement,
+    XBoardGame,
+};
 
 pub struct Engine {
     quit: bool,
     cmdline: Cmdline, // Command line interpreter.
     settings: Settings,
+    options: Arc<Vec<EngineOption>>, // Options exposed to the GUI.
     board: Arc<Mutex<Board>>,
     pub comm: Box<dyn IComm>,              // Communications (active).
+    transport: Transport,                  // Connection used by Comm.
     tt_search: Arc<Mutex<TT<SearchData>>>, // TT for search information.
+    book: Arc<Mutex<Arc<MoveBook>>>,       // Opening book used by the search.
     movegen: Arc<MoveGenerator>,
     search: Search,
+    xboard: XBoardGame, // Game state when running under XBoard.
     pub info_receiver: Option<Receiver<Information>>, // Receiver for incoming information.
 }
 
 impl Engine {
-    pub fn new() -> Self {
-        // Determine if the compiled engine is 32 or 64-bit
-        //let is_64_bit = std::mem::size_of::<usize>() == 8;
-
+    // Create an engine that talks to its GUI or client over 'transport'.
+    pub fn new(transport: Transport) -> Self {
         let cmdline = Cmdline::new();
         // Get engine settings from the command-line.
         let threads = cmdline.threads();
         let quiet = cmdline.has_quiet();
         let tt_size = cmdline.hash();
+        let log_file = cmdline.log();
 
-        let comm = Box::new(Uci::new());
+        // Create the communication interface selected on the command line.
+        let comm: Box<dyn IComm> = match &cmdline.comm()[..] {
+            "xboard" => Box::new(XBoard::new(transport.clone())),
+            "json" => Box::new(Json::new(transport.clone())),
+            "console" => Box::new(Console::new(transport.clone())),
+            _ => Box::new(Uci::new(transport.clone())),
+        };
 
-        // let tt_max = if is_64_bit {
-        //     EngineOptionDefaults::HASH_MAX_64_BIT
-        // } else {
-        //     EngineOptionDefaults::HASH_MAX_32_BIT
-        // };
+        // Determine if the compiled engine is 32 or 64-bit
+        let tt_max = if std::mem::size_of::<usize>() == 8 {
+            EngineOptionDefaults::HASH_MAX_64_BIT
+        } else {
+            EngineOptionDefaults::HASH_MAX_32_BIT
+        };
 
+        let tt_size = tt_size.min(tt_max);
         let tt_search: Arc<Mutex<TT<SearchData>>>;
         tt_search = Arc::new(Mutex::new(TT::<SearchData>::new(tt_size)));
 
+        // Options the engine exposes to the GUI.
+        let options = vec![
+            EngineOption::new(
+                EngineOptionNames::HASH,
+                UiElement::Spin,
+                Some(tt_size.to_string()),
+                Some(EngineOptionDefaults::HASH_MIN.to_string()),
+                Some(tt_max.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionNames::CLEAR_HASH,
+                UiElement::Button,
+                None,
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionNames::THREADS,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::THREADS_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::THREADS_MIN.to_string()),
+                Some(EngineOptionDefaults::THREADS_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionNames::MOVE_OVERHEAD,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::MOVE_OVERHEAD_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::MOVE_OVERHEAD_MIN.to_string()),
+                Some(EngineOptionDefaults::MOVE_OVERHEAD_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionNames::OWN_BOOK,
+                UiElement::Check,
+                Some(EngineOptionDefaults::OWN_BOOK_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionNames::BOOK_FILE,
+                UiElement::String,
+                Some(EngineOptionDefaults::BOOK_FILE_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionNames::MULTI_PV,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::MULTI_PV_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::MULTI_PV_MIN.to_string()),
+                Some(EngineOptionDefaults::MULTI_PV_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionNames::PONDER,
+                UiElement::Check,
+                Some(EngineOptionDefaults::PONDER_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionNames::CHESS960,
+                UiElement::Check,
+                Some(EngineOptionDefaults::CHESS960_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionNames::SHOW_WDL,
+                UiElement::Check,
+                Some(EngineOptionDefaults::SHOW_WDL_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionNames::SKILL_LEVEL,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::SKILL_LEVEL_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::SKILL_LEVEL_MIN.to_string()),
+                Some(EngineOptionDefaults::SKILL_LEVEL_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionNames::LIMIT_STRENGTH,
+                UiElement::Check,
+                Some(EngineOptionDefaults::LIMIT_STRENGTH_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionNames::ELO,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::ELO_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::ELO_MIN.to_string()),
+                Some(EngineOptionDefaults::ELO_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionNames::DEBUG_LOG_FILE,
+                UiElement::String,
+                Some(log_file.clone()),
+                None,
+                None,
+            ),
+        ];
+
         Self {
             quit: false,
             settings: Settings {
                 threads,
                 quiet,
                 tt_size,
+                move_overhead: EngineOptionDefaults::MOVE_OVERHEAD_DEFAULT,
+                own_book: EngineOptionDefaults::OWN_BOOK_DEFAULT,
+                book_file: EngineOptionDefaults::BOOK_FILE_DEFAULT.to_string(),
+                multi_pv: EngineOptionDefaults::MULTI_PV_DEFAULT,
+                ponder: EngineOptionDefaults::PONDER_DEFAULT,
+                chess960: EngineOptionDefaults::CHESS960_DEFAULT,
+                show_wdl: EngineOptionDefaults::SHOW_WDL_DEFAULT,
+                skill_level: EngineOptionDefaults::SKILL_LEVEL_DEFAULT,
+                limit_strength: EngineOptionDefaults::LIMIT_STRENGTH_DEFAULT,
+                elo: EngineOptionDefaults::ELO_DEFAULT,
+                log_file,
+                debug: false,
             },
+            options: Arc::new(options),
             tt_search,
+            book: Arc::new(Mutex::new(Arc::new(MoveBook::new()))),
             comm,
+            transport,
             cmdline,
             board: Arc::new(Mutex::new(Board::new())),
             movegen: Arc::new(MoveGenerator::new()),
             search: Search::new(),
+            xboard: XBoardGame::new(),
             info_receiver: None,
         }
     }
@@ -72,6 +208,11 @@ impl Engine {
     pub fn run(&mut self) -> EngineRunResult {
         self.setup_position()?;
 
+        // Run a test suite instead, if one was given.
+        if let Some(file) = self.cmdline.epd_test() {
+            return self.epd_test(&file);
+        }
+
         // engine runs in the main loop where it checks for legal moves.
         self.main_loop();
 
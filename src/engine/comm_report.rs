@@ -1,9 +1,19 @@
 use crate::{
-    comm::{uci::UciReport, CommControl, CommReport}, defs::FEN_START_POSITION, puzzle::Puzzle, search::defs::{SearchControl, SearchMode, SearchParams, SearchRefs, SearchType}
+    comm::{
+        uci::UciReport,
+        xboard::{TimeControl, XBoardReport},
+        CommControl, CommReport,
+    },
+    defs::{EngineError, Sides, FEN_START_POSITION},
+    engine::defs::{EngineOptionDefaults, EngineOptionName, EngineOptionNames},
+    puzzle::Puzzle,
+    search::{
+        defs::{GameTime, SearchControl, SearchMode, SearchParams, SearchRefs, SearchType},
+        skill::Skill,
+    },
 };
-use std::env;
-use std::fs::File;
 use std::error::Error;
+use std::fs::File;
 
 use super::Engine;
 
@@ -11,17 +21,33 @@ impl Engine {
     pub fn comm_reports(&mut self, comm: &CommReport) {
         match comm {
             CommReport::Uci(c) => self.comm_report_uci(c),
+            CommReport::XBoard(c) => self.comm_report_xboard(c),
         }
     }
 
-    fn comm_report_uci(&mut self, ucireport: &UciReport) {
-        let mut sp = SearchParams::new();
+    // Apply the engine settings to search parameters which contain the
+    // limits for a search.
+    fn with_settings(&self, mut sp: SearchParams) -> SearchParams {
         sp.quiet = self.settings.quiet;
+        sp.move_overhead = self.settings.move_overhead;
+        sp.multi_pv = self.settings.multi_pv;
+        sp.show_wdl = self.settings.show_wdl;
+        sp.skill_level = self.skill().level();
+        sp.tt_enabled = self.settings.tt_size > 0;
+        sp
+    }
+
+    fn comm_report_uci(&mut self, ucireport: &UciReport) {
         match ucireport {
             UciReport::Uci => {
                 self.comm.send(CommControl::Identify);
             }
 
+            UciReport::Debug(on) => {
+                self.settings.debug = *on;
+                self.debug(String::from("debug mode on"));
+            }
+
             UciReport::UciNewGame => {
                 let _ = self
                     .board
@@ -33,7 +59,10 @@ impl Engine {
                 self.comm.send(CommControl::Ready);
             }
 
-            // UciReport::SetOption(option) => (),
+            UciReport::SetOption(option) => {
+                self.set_option(option);
+                self.debug(format!("option set: {option:?}"));
+            }
             UciReport::Position(fen, moves) => {
                 let fen_result = self
                     .board
@@ -41,75 +70,432 @@ impl Engine {
                     .expect("Error locking board")
                     .read_fen(Some(fen));
 
-                if fen_result.is_ok() {
-                    for m in moves.iter() {
-                        let ok = self.execute_move(m.clone());
-                        if !ok {
-                            let msg = format!("{}: {}", m, "illigal move");
-                            self.comm.send(CommControl::InfoString(msg));
-                            break;
+                match fen_result {
+                    Ok(()) => {
+                        for m in moves.iter() {
+                            if self.execute_move(m.clone()).is_err() {
+                                self.comm.send(CommControl::IllegalMove(m.clone()));
+                                break;
+                            }
                         }
                     }
+                    Err(e) => self.comm.send(CommControl::Error(e.into())),
                 }
+
+                let fen = self.board.lock().expect("Error locking board").create_fen();
+                self.debug(format!("position: {fen}"));
             }
-            UciReport::GoInfinite => {
-                sp.search_mode = SearchMode::Infinite;
-                self.search
-                    .send(SearchControl::Start(sp, SearchType::Search));
-            }
-            UciReport::GoDepth(depth) => {
-                sp.depth = *depth;
-                sp.search_mode = SearchMode::Depth;
+            UciReport::Go(limits, search_moves) => {
+                let mut sp = self.with_settings(limits.clone());
+                sp.search_moves = match self.search_moves(search_moves) {
+                    Ok(moves) => moves,
+                    Err(e) => {
+                        self.comm.send(CommControl::Error(e));
+                        return;
+                    }
+                };
+                let gt = &sp.game_time;
+                self.debug(format!(
+                    "search: limits {:?} depth {} movetime {} nodes {} wtime {} btime {} winc {} binc {} overhead {}",
+                    sp.search_modes,
+                    sp.depth,
+                    sp.move_time,
+                    sp.nodes,
+                    gt.white_time,
+                    gt.black_time,
+                    gt.white_time_incr,
+                    gt.black_time_incr,
+                    sp.move_overhead
+                ));
                 self.search
                     .send(SearchControl::Start(sp, SearchType::Search));
             }
             UciReport::GoPerft(depth) => {
+                let mut sp = self.with_settings(SearchParams::new());
                 sp.depth = *depth;
-                sp.search_mode = SearchMode::Depth;
                 self.search
                     .send(SearchControl::Start(sp, SearchType::Perft));
             }
-            UciReport::GoMoveTime(t) => {
-                sp.move_time = *t;
-                sp.search_mode = SearchMode::MoveTime;
-                self.search
-                    .send(SearchControl::Start(sp, SearchType::Search));
-            }
 
-            UciReport::GoNodes(nodes) => {
-                sp.nodes = *nodes;
-                sp.search_mode = SearchMode::Nodes;
-                self.search
-                    .send(SearchControl::Start(sp, SearchType::Search));
-            }
-
-            UciReport::GoGameTime(gt) => {
-                sp.game_time = *gt;
-                sp.search_mode = SearchMode::GameTime;
+            UciReport::Bench(depth) => {
+                let mut sp = self.with_settings(SearchParams::new());
+                sp.depth = *depth;
                 self.search
-                    .send(SearchControl::Start(sp, SearchType::Search));
+                    .send(SearchControl::Start(sp, SearchType::Bench));
             }
 
             UciReport::Puzzle => {
-                let path = env::current_dir().unwrap();
-                let formatted_path = format!("{}/../sorted_puzzles.csv", path.display());
+                let file = "../sorted_puzzles.csv";
 
-                match Puzzle::read_puzzles_from_csv(&formatted_path) {
+                match Puzzle::read_puzzles_from_csv(file) {
                     Ok(puzzles) => {
+                        let sp = self.with_settings(SearchParams::new());
                         for puzzle in puzzles {
-                            self.solve_puzzle(puzzle, sp);
+                            if let Err(e) = self.solve_puzzle(puzzle, sp.clone()) {
+                                self.comm.send(CommControl::Error(e));
+                            }
                         }
                     }
-                    Err(e) => println!("Error reading puzzles from file: {}", e),
+                    Err(e) => {
+                        let error = EngineError::Io(file.to_string(), e.to_string());
+                        self.comm.send(CommControl::Error(error));
+                    }
                 };
-
-
-            },
+            }
 
             UciReport::Quit => self.quit(),
             UciReport::Stop => self.search.send(SearchControl::Stop),
+            UciReport::PonderHit => self.search.send(SearchControl::PonderHit),
             UciReport::Board => self.comm.send(CommControl::PrintBoard),
             UciReport::Unknown => (),
         }
     }
 }
+
+// Options set by the GUI. Values that can't be parsed are reported back
+// and leave the current setting unchanged.
+impl Engine {
+    fn set_option(&mut self, option: &EngineOptionName) {
+        match option {
+            EngineOptionName::Hash(value) => {
+                if let Ok(v) = value.parse::<usize>() {
+                    let v = v.clamp(EngineOptionDefaults::HASH_MIN, self.tt_max());
+                    self.tt_search.lock().expect("error locking TT").resize(v);
+                    self.settings.tt_size = v;
+                } else {
+                    self.option_error(EngineOptionNames::HASH, value);
+                }
+            }
+
+            EngineOptionName::ClearHash => {
+                self.tt_search.lock().expect("error locking TT").clear();
+            }
+
+            EngineOptionName::Threads(value) => {
+                if let Ok(v) = value.parse::<usize>() {
+                    self.settings.threads = v.clamp(
+                        EngineOptionDefaults::THREADS_MIN,
+                        EngineOptionDefaults::THREADS_MAX,
+                    );
+                } else {
+                    self.option_error(EngineOptionNames::THREADS, value);
+                }
+            }
+
+            EngineOptionName::MoveOverhead(value) => {
+                if let Ok(v) = value.parse::<u128>() {
+                    self.settings.move_overhead = v.clamp(
+                        EngineOptionDefaults::MOVE_OVERHEAD_MIN,
+                        EngineOptionDefaults::MOVE_OVERHEAD_MAX,
+                    );
+                } else {
+                    self.option_error(EngineOptionNames::MOVE_OVERHEAD, value);
+                }
+            }
+
+            EngineOptionName::OwnBook(value) => {
+                if let Ok(v) = value.parse::<bool>() {
+                    self.settings.own_book = v;
+                    self.load_book();
+                } else {
+                    self.option_error(EngineOptionNames::OWN_BOOK, value);
+                }
+            }
+
+            EngineOptionName::BookFile(value) => {
+                self.settings.book_file = value.clone();
+                self.load_book();
+            }
+
+            EngineOptionName::MultiPv(value) => {
+                if let Ok(v) = value.parse::<u8>() {
+                    self.settings.multi_pv = v.clamp(
+                        EngineOptionDefaults::MULTI_PV_MIN,
+                        EngineOptionDefaults::MULTI_PV_MAX,
+                    );
+                } else {
+                    self.option_error(EngineOptionNames::MULTI_PV, value);
+                }
+            }
+
+            EngineOptionName::Ponder(value) => {
+                if let Ok(v) = value.parse::<bool>() {
+                    self.settings.ponder = v;
+                } else {
+                    self.option_error(EngineOptionNames::PONDER, value);
+                }
+            }
+
+            // The board needs to know, as it determines how castling
+            // moves are written and read.
+            EngineOptionName::Chess960(value) => {
+                if let Ok(v) = value.parse::<bool>() {
+                    self.settings.chess960 = v;
+                    self.board.lock().expect("error locking board").chess960 = v;
+                } else {
+                    self.option_error(EngineOptionNames::CHESS960, value);
+                }
+            }
+
+            EngineOptionName::ShowWdl(value) => {
+                if let Ok(v) = value.parse::<bool>() {
+                    self.settings.show_wdl = v;
+                } else {
+                    self.option_error(EngineOptionNames::SHOW_WDL, value);
+                }
+            }
+
+            EngineOptionName::SkillLevel(value) => {
+                if let Ok(v) = value.parse::<u8>() {
+                    self.settings.skill_level = v.clamp(
+                        EngineOptionDefaults::SKILL_LEVEL_MIN,
+                        EngineOptionDefaults::SKILL_LEVEL_MAX,
+                    );
+                } else {
+                    self.option_error(EngineOptionNames::SKILL_LEVEL, value);
+                }
+            }
+
+            EngineOptionName::LimitStrength(value) => {
+                if let Ok(v) = value.parse::<bool>() {
+                    self.settings.limit_strength = v;
+                } else {
+                    self.option_error(EngineOptionNames::LIMIT_STRENGTH, value);
+                }
+            }
+
+            EngineOptionName::Elo(value) => {
+                if let Ok(v) = value.parse::<u16>() {
+                    self.settings.elo =
+                        v.clamp(EngineOptionDefaults::ELO_MIN, EngineOptionDefaults::ELO_MAX);
+                } else {
+                    self.option_error(EngineOptionNames::ELO, value);
+                }
+            }
+
+            EngineOptionName::DebugLogFile(value) => self.set_log_file(value),
+
+            EngineOptionName::Nothing => (),
+        }
+    }
+
+    // The playing strength: the Elo rating if strength is limited, or
+    // else the skill level.
+    fn skill(&self) -> Skill {
+        if self.settings.limit_strength {
+            Skill::from_elo(self.settings.elo)
+        } else {
+            Skill::new(self.settings.skill_level)
+        }
+    }
+
+    fn option_error(&self, name: &str, value: &str) {
+        let msg = format!("{name}: invalid value '{value}'");
+        self.comm
+            .send(CommControl::Error(EngineError::Protocol(msg)));
+    }
+}
+
+// XBoard keeps the game in the engine: the engine decides by itself when it
+// has to think, and plays the move it finds on its own board.
+impl Engine {
+    fn comm_report_xboard(&mut self, xboardreport: &XBoardReport) {
+        match xboardreport {
+            XBoardReport::XBoard => (),
+            XBoardReport::ProtoVer(_) => self.comm.send(CommControl::Identify),
+
+            XBoardReport::New => {
+                self.xboard_abort_search();
+                let _ = self
+                    .board
+                    .lock()
+                    .expect("error locking board")
+                    .read_fen(Some(FEN_START_POSITION));
+                self.xboard.force = false;
+                self.xboard.analyze = false;
+                self.xboard.max_depth = None;
+                self.xboard.hint = None;
+            }
+
+            XBoardReport::Force => {
+                self.xboard_abort_search();
+                self.xboard.force = true;
+            }
+
+            XBoardReport::Go => {
+                self.xboard.force = false;
+                self.xboard_think();
+            }
+
+            XBoardReport::PlayOther => self.xboard.force = false,
+
+            XBoardReport::SetBoard(fen) => {
+                self.xboard_abort_search();
+                self.xboard.hint = None;
+                let fen_result = self
+                    .board
+                    .lock()
+                    .expect("error locking board")
+                    .read_fen(Some(fen));
+
+                if let Err(e) = fen_result {
+                    self.comm.send(CommControl::Error(e.into()));
+                }
+                self.xboard_restart_analysis();
+            }
+
+            XBoardReport::UserMove(m) => {
+                self.xboard_abort_search();
+                if self.execute_move(m.clone()).is_ok() {
+                    self.xboard.hint = None;
+                    if self.xboard.analyze {
+                        self.xboard_restart_analysis();
+                    } else if !self.xboard.force {
+                        self.xboard_think();
+                    }
+                } else {
+                    self.comm.send(CommControl::IllegalMove(m.clone()));
+                }
+            }
+
+            XBoardReport::TimeControl(tc) => self.xboard.time_control = *tc,
+            XBoardReport::Sd(depth) => self.xboard.max_depth = Some(*depth),
+            XBoardReport::Time(t) => self.xboard.engine_time = *t,
+            XBoardReport::OTim(t) => self.xboard.opponent_time = *t,
+
+            XBoardReport::Analyze => {
+                self.xboard_abort_search();
+                self.xboard.analyze = true;
+                self.xboard_restart_analysis();
+            }
+
+            XBoardReport::Exit => {
+                self.xboard_abort_search();
+                self.xboard.analyze = false;
+            }
+
+            XBoardReport::Undo | XBoardReport::Remove => {
+                self.xboard_abort_search();
+                let count = if *xboardreport == XBoardReport::Remove {
+                    2
+                } else {
+                    1
+                };
+                let mut board = self.board.lock().expect("error locking board");
+                for _ in 0..count {
+                    if board.history.len() > 0 {
+                        board.unmake();
+                    }
+                }
+                std::mem::drop(board);
+                self.xboard.hint = None;
+                self.xboard_restart_analysis();
+            }
+
+            XBoardReport::Result(_) => {
+                self.xboard_abort_search();
+                self.xboard.force = true;
+            }
+
+            XBoardReport::Post => self.xboard.post = true,
+            XBoardReport::NoPost => self.xboard.post = false,
+            XBoardReport::Ping(n) => self.comm.send(CommControl::Pong(*n)),
+
+            // Stop thinking; the search reports the best move found so far.
+            XBoardReport::MoveNow => {
+                if self.xboard.thinking {
+                    self.search.send(SearchControl::Stop);
+                }
+            }
+
+            // Suggest the reply the engine expected to its last move.
+            XBoardReport::Hint => match self.xboard.hint {
+                Some(m) => self.comm.send(CommControl::Hint(m)),
+                None => {
+                    let msg = String::from("no hint available");
+                    self.comm.send(CommControl::InfoString(msg));
+                }
+            },
+
+            XBoardReport::Quit => self.quit(),
+            XBoardReport::Board => self.comm.send(CommControl::PrintBoard),
+            XBoardReport::Unknown => (),
+        }
+    }
+
+    // Start searching for a move to play in the current position.
+    fn xboard_think(&mut self) {
+        let sp = self.xboard_search_params();
+        self.xboard.thinking = true;
+        self.xboard.searching = true;
+        self.search
+            .send(SearchControl::Start(sp, SearchType::Search));
+    }
+
+    // In analyze mode the engine searches every new position until the
+    // GUI tells it to stop.
+    fn xboard_restart_analysis(&mut self) {
+        if self.xboard.analyze {
+            let mut sp = self.with_settings(SearchParams::new());
+            sp.add_mode(SearchMode::Infinite);
+            self.xboard.searching = true;
+            self.search
+                .send(SearchControl::Start(sp, SearchType::Search));
+        }
+    }
+
+    // Stop a running search. Its result will arrive later and has to be
+    // ignored, because the position it belongs to is gone. A search that
+    // already finished on its own has nothing left to ignore.
+    fn xboard_abort_search(&mut self) {
+        if self.xboard.searching {
+            self.xboard.thinking = false;
+            self.xboard.searching = false;
+            self.xboard.discard += 1;
+            self.search.send(SearchControl::Stop);
+        }
+    }
+
+    // Translate the XBoard time control and clocks into search parameters.
+    fn xboard_search_params(&self) -> SearchParams {
+        let mut sp = self.with_settings(SearchParams::new());
+
+        match self.xboard.time_control {
+            TimeControl::SecondsPerMove(t) => {
+                sp.move_time = t;
+                sp.add_mode(SearchMode::MoveTime);
+            }
+            TimeControl::Level(mps, _, inc) => {
+                let board = self.board.lock().expect("error locking board");
+                let white = board.side_to_move() == Sides::WHITE;
+                let fullmove = board.gamestate.fullmove_number.max(1) as usize;
+                std::mem::drop(board);
+
+                let (engine, opponent) = (self.xboard.engine_time, self.xboard.opponent_time);
+                let (wtime, btime) = if white {
+                    (engine, opponent)
+                } else {
+                    (opponent, engine)
+                };
+
+                // With a conventional clock, count the moves until the next
+                // time control is reached.
+                let moves_to_go = if mps > 0 {
+                    Some(mps - ((fullmove - 1) % mps))
+                } else {
+                    None
+                };
+
+                sp.game_time = GameTime::new(wtime, btime, inc, inc, moves_to_go);
+                sp.add_mode(SearchMode::GameTime);
+            }
+        }
+
+        if let Some(depth) = self.xboard.max_depth {
+            sp.depth = depth;
+            sp.add_mode(SearchMode::Depth);
+        }
+
+        sp
+    }
+}
@@ -1,10 +1,67 @@
-use crate::{comm::CommReport, search::defs::SearchReport};
+use serde::Serialize;
+
+use crate::{
+    comm::{xboard::TimeControl, CommReport},
+    movegen::defs::Move,
+    search::{
+        defs::SearchReport,
+        skill::{ELO_MAX, ELO_MIN, SKILL_LEVEL_MAX, SKILL_LEVEL_MIN},
+    },
+};
 
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub threads: usize,
     pub quiet: bool,
     pub tt_size: usize,
+    pub move_overhead: u128,
+    pub own_book: bool,
+    pub book_file: String,
+    pub multi_pv: u8,
+    pub ponder: bool,
+    pub chess960: bool,
+    pub show_wdl: bool,
+    pub skill_level: u8,
+    pub limit_strength: bool, // Play at the rating in 'elo' instead.
+    pub elo: u16,
+    pub log_file: String, // Transcript of the session; none if empty.
+    pub debug: bool,      // Send extra diagnostics to the GUI.
+}
+
+// Contrary to UCI, the XBoard protocol expects the engine to keep track of
+// the game, the clocks and when it should move by itself. This struct holds
+// that state.
+#[derive(Debug, Clone)]
+pub struct XBoardGame {
+    pub force: bool,               // Only register moves; don't think.
+    pub analyze: bool,             // Analyze mode: search until 'exit'.
+    pub post: bool,                // Send thinking output.
+    pub thinking: bool,            // A search for a game move is running.
+    pub searching: bool,           // A search of any kind is running.
+    pub discard: usize,            // Number of aborted searches to ignore.
+    pub time_control: TimeControl, // Time control set by the GUI.
+    pub max_depth: Option<i8>,     // Depth limit set by 'sd'.
+    pub engine_time: u128,         // Time left on the engine's clock.
+    pub opponent_time: u128,       // Time left on the opponent's clock.
+    pub hint: Option<Move>,        // Expected reply to the engine's move.
+}
+
+impl XBoardGame {
+    pub fn new() -> Self {
+        Self {
+            force: false,
+            analyze: false,
+            post: true,
+            thinking: false,
+            searching: false,
+            discard: 0,
+            time_control: TimeControl::Level(0, 5 * 60 * 1000, 0),
+            max_depth: None,
+            engine_time: 5 * 60 * 1000,
+            opponent_time: 5 * 60 * 1000,
+            hint: None,
+        }
+    }
 }
 
 // This enum provides informatin to the engine, with regard to incoming
@@ -15,18 +72,86 @@ pub enum Information {
     Search(SearchReport),
 }
 
-#[derive(PartialEq, Clone)]
+// Options the engine accepts from the GUI through "setoption". The value
+// is kept as a string; the engine parses it when applying the option.
+#[derive(PartialEq, Clone, Debug)]
 pub enum EngineOptionName {
     Hash(String),
     ClearHash,
+    Threads(String),
+    MoveOverhead(String),
+    OwnBook(String),
+    BookFile(String),
+    MultiPv(String),
+    Ponder(String),
+    Chess960(String),
+    ShowWdl(String),
+    SkillLevel(String),
+    LimitStrength(String),
+    Elo(String),
+    DebugLogFile(String),
     Nothing,
 }
+
+impl EngineOptionName {
+    // Find the option by its advertised name. Option names are case
+    // insensitive; unknown options become Nothing.
+    pub fn new(name: &str, value: String) -> Self {
+        let name = name.to_lowercase();
+        match name {
+            n if n == EngineOptionNames::HASH.to_lowercase() => Self::Hash(value),
+            n if n == EngineOptionNames::CLEAR_HASH.to_lowercase() => Self::ClearHash,
+            n if n == EngineOptionNames::THREADS.to_lowercase() => Self::Threads(value),
+            n if n == EngineOptionNames::MOVE_OVERHEAD.to_lowercase() => Self::MoveOverhead(value),
+            n if n == EngineOptionNames::OWN_BOOK.to_lowercase() => Self::OwnBook(value),
+            n if n == EngineOptionNames::BOOK_FILE.to_lowercase() => Self::BookFile(value),
+            n if n == EngineOptionNames::MULTI_PV.to_lowercase() => Self::MultiPv(value),
+            n if n == EngineOptionNames::PONDER.to_lowercase() => Self::Ponder(value),
+            n if n == EngineOptionNames::CHESS960.to_lowercase() => Self::Chess960(value),
+            n if n == EngineOptionNames::SHOW_WDL.to_lowercase() => Self::ShowWdl(value),
+            n if n == EngineOptionNames::SKILL_LEVEL.to_lowercase() => Self::SkillLevel(value),
+            n if n == EngineOptionNames::LIMIT_STRENGTH.to_lowercase() => {
+                Self::LimitStrength(value)
+            }
+            n if n == EngineOptionNames::ELO.to_lowercase() => Self::Elo(value),
+            n if n == EngineOptionNames::DEBUG_LOG_FILE.to_lowercase() => Self::DebugLogFile(value),
+            _ => Self::Nothing,
+        }
+    }
+}
+
+// Names of the options, as advertised to the GUI.
+pub struct EngineOptionNames;
+impl EngineOptionNames {
+    pub const HASH: &'static str = "Hash";
+    pub const CLEAR_HASH: &'static str = "Clear Hash";
+    pub const THREADS: &'static str = "Threads";
+    pub const MOVE_OVERHEAD: &'static str = "Move Overhead";
+    pub const OWN_BOOK: &'static str = "OwnBook";
+    pub const BOOK_FILE: &'static str = "BookFile";
+    pub const MULTI_PV: &'static str = "MultiPV";
+    pub const PONDER: &'static str = "Ponder";
+    pub const CHESS960: &'static str = "UCI_Chess960";
+    pub const SHOW_WDL: &'static str = "UCI_ShowWDL";
+    pub const SKILL_LEVEL: &'static str = "Skill Level";
+    pub const LIMIT_STRENGTH: &'static str = "UCI_LimitStrength";
+    pub const ELO: &'static str = "UCI_Elo";
+    pub const DEBUG_LOG_FILE: &'static str = "Debug Log File";
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum UiElement {
     Spin,
+    Check,
     Button,
+    String,
 }
+
+#[derive(Serialize)]
 pub struct EngineOption {
     pub name: &'static str,
+    #[serde(rename = "type")]
     pub ui_element: UiElement,
     pub default: Option<String>,
     pub min: Option<String>,
@@ -57,4 +182,25 @@ impl EngineOptionDefaults {
     pub const HASH_MIN: usize = 0;
     pub const HASH_MAX_64_BIT: usize = 65536;
     pub const HASH_MAX_32_BIT: usize = 2048;
+    pub const THREADS_DEFAULT: usize = 1;
+    pub const THREADS_MIN: usize = 1;
+    pub const THREADS_MAX: usize = 1; // The search is single-threaded.
+    pub const MOVE_OVERHEAD_DEFAULT: u128 = 50;
+    pub const MOVE_OVERHEAD_MIN: u128 = 0;
+    pub const MOVE_OVERHEAD_MAX: u128 = 5000;
+    pub const OWN_BOOK_DEFAULT: bool = true;
+    pub const BOOK_FILE_DEFAULT: &'static str = "../book.txt";
+    pub const MULTI_PV_DEFAULT: u8 = 1;
+    pub const MULTI_PV_MIN: u8 = 1;
+    pub const MULTI_PV_MAX: u8 = 64;
+    pub const PONDER_DEFAULT: bool = false;
+    pub const CHESS960_DEFAULT: bool = false;
+    pub const SHOW_WDL_DEFAULT: bool = false;
+    pub const SKILL_LEVEL_DEFAULT: u8 = SKILL_LEVEL_MAX;
+    pub const SKILL_LEVEL_MIN: u8 = SKILL_LEVEL_MIN;
+    pub const SKILL_LEVEL_MAX: u8 = SKILL_LEVEL_MAX;
+    pub const LIMIT_STRENGTH_DEFAULT: bool = false;
+    pub const ELO_DEFAULT: u16 = 1500;
+    pub const ELO_MIN: u16 = ELO_MIN;
+    pub const ELO_MAX: u16 = ELO_MAX;
 }
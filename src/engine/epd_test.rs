@@ -0,0 +1,201 @@
+use std::{fs, sync::Arc, time::Instant};
+
+use crate::{
+    defs::{EngineError, EngineRunResult},
+    extra::{epd::Epd, san::move_to_san},
+    movegen::defs::Move,
+    search::defs::{SearchControl, SearchMode, SearchParams, SearchReport, SearchType},
+};
+
+use super::{defs::Information, Engine};
+
+// Number of solved positions in one test suite.
+struct SuiteScore {
+    name: String,
+    solved: usize,
+    total: usize,
+}
+
+// How the engine did on one position.
+enum Outcome {
+    Solved,
+    Failed,
+    Skipped(String),
+}
+
+impl Engine {
+    // Search each position of an EPD file and check the move the engine
+    // plays against the "bm" (best move), "am" (avoid move) and "dm" (mate
+    // in n) operations. The positions are grouped into suites by their id:
+    // "WAC.001" is part of the suite "WAC".
+    pub fn epd_test(&mut self, file: &str) -> EngineRunResult {
+        let text = fs::read_to_string(file).map_err(|e| EngineError::io(file, &e))?;
+
+        let (info_sender, info_receiver) = crossbeam_channel::unbounded::<Information>();
+        self.search.init(
+            info_sender,
+            Arc::clone(&self.board),
+            Arc::clone(&self.movegen),
+            Arc::clone(&self.tt_search),
+            Arc::clone(&self.book),
+        );
+
+        let start = Instant::now();
+        let mut suites: Vec<SuiteScore> = Vec::new();
+
+        for (nr, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let id = format!("{file}:{}", nr + 1);
+            let epd = match Epd::parse(line) {
+                Ok(epd) => epd,
+                Err(e) => {
+                    self.transport.write_line(&format!("{id:<16} skipped  {e}"));
+                    continue;
+                }
+            };
+            let id = epd.id().map(String::from).unwrap_or(id);
+
+            let outcome = self.epd_position(&epd, &id, &info_receiver);
+            if let Outcome::Skipped(reason) = outcome {
+                self.transport
+                    .write_line(&format!("{id:<16} skipped  {reason}"));
+                continue;
+            }
+
+            // "WAC.001" belongs to "WAC"; an id without a number is a suite
+            // of its own.
+            let suite = id.rsplit_once('.').map_or(id.as_str(), |(s, _)| s);
+            let score = match suites.iter_mut().find(|s| s.name == suite) {
+                Some(score) => score,
+                None => {
+                    suites.push(SuiteScore {
+                        name: suite.to_string(),
+                        solved: 0,
+                        total: 0,
+                    });
+                    suites.last_mut().expect("just added")
+                }
+            };
+            score.total += 1;
+            score.solved += matches!(outcome, Outcome::Solved) as usize;
+        }
+
+        self.search.send(SearchControl::Quit);
+        self.search.wait_for_shutdown();
+
+        self.transport.write_line("");
+        for s in suites.iter() {
+            self.epd_score(&s.name, s.solved, s.total);
+        }
+        let solved = suites.iter().map(|s| s.solved).sum();
+        let total = suites.iter().map(|s| s.total).sum();
+        self.epd_score("Total", solved, total);
+
+        let secs = start.elapsed().as_secs();
+        self.transport.write_line(&format!("Time: {secs} s"));
+
+        Ok(())
+    }
+
+    fn epd_position(
+        &mut self,
+        epd: &Epd,
+        id: &str,
+        info_receiver: &crossbeam_channel::Receiver<Information>,
+    ) -> Outcome {
+        let fen = epd.fen();
+        let mut board = self.board.lock().expect("error locking board").clone();
+        if let Err(e) = board.read_fen(Some(&fen)) {
+            return Outcome::Skipped(format!("invalid position: {e}"));
+        }
+
+        let best_moves = epd.best_moves(&mut board, &self.movegen);
+        let avoid_moves = epd.avoid_moves(&mut board, &self.movegen);
+        let mate = epd.direct_mate();
+        if best_moves.is_empty() && avoid_moves.is_empty() && mate.is_none() {
+            return Outcome::Skipped(String::from("no legal bm, am or dm"));
+        }
+
+        // Each position is searched as if it were the first.
+        *self.board.lock().expect("error locking board") = board.clone();
+        self.tt_search.lock().expect("error locking TT").clear();
+
+        let mut sp = SearchParams::new();
+        sp.quiet = true;
+        sp.tt_enabled = self.settings.tt_size > 0;
+        sp.move_time = self.cmdline.epd_time();
+        sp.add_mode(SearchMode::MoveTime);
+        if let Some(depth) = epd.depth().or(self.cmdline.epd_depth()) {
+            sp.depth = depth;
+            sp.add_mode(SearchMode::Depth);
+        }
+        if let Some(m) = mate {
+            sp.mate = m;
+            sp.add_mode(SearchMode::Mate);
+        }
+
+        self.search
+            .send(SearchControl::Start(sp, SearchType::Search));
+
+        // Wait for the best move. The last summary tells how deep the
+        // search went, and if it found a mate.
+        let mut depth = 0;
+        let mut mate_found = 0;
+        let best_move = loop {
+            match info_receiver.recv() {
+                Ok(Information::Search(SearchReport::SearchSummary(s))) => {
+                    depth = s.depth;
+                    mate_found = s.mate;
+                }
+                Ok(Information::Search(SearchReport::Finished(m, _))) => break m,
+                Ok(_) => (),
+                Err(_) => return Outcome::Skipped(String::from("search stopped")),
+            }
+        };
+
+        let is = |moves: &[Move]| moves.iter().any(|m| m.get_move() == best_move.get_move());
+        let solved = (best_moves.is_empty() || is(&best_moves))
+            && !is(&avoid_moves)
+            && mate.is_none_or(|m| mate_found > 0 && mate_found <= m as i16);
+
+        let played = if best_move.get_move() == 0 {
+            String::from("-")
+        } else {
+            move_to_san(&mut board, &self.movegen, best_move)
+        };
+        let expected = epd
+            .operations
+            .iter()
+            .filter(|(op, _)| ["bm", "am", "dm"].contains(&op.as_str()))
+            .map(|(op, operands)| format!("{op} {}", operands.join(" ")))
+            .collect::<Vec<String>>()
+            .join("; ");
+        let status = if solved { "solved" } else { "failed" };
+
+        // The comment may say what the position is about.
+        let comment = match epd.comment() {
+            Some(c) if !solved => format!("  ({c})"),
+            _ => String::new(),
+        };
+
+        self.transport.write_line(&format!(
+            "{id:<16} {status:<8} {played:<8} {expected:<20} depth {depth}{comment}"
+        ));
+
+        if solved {
+            Outcome::Solved
+        } else {
+            Outcome::Failed
+        }
+    }
+
+    fn epd_score(&self, name: &str, solved: usize, total: usize) {
+        let percentage = (solved * 100).checked_div(total).unwrap_or(0);
+        self.transport.write_line(&format!(
+            "{name:<16} {solved}/{total} solved ({percentage}%)"
+        ));
+    }
+}
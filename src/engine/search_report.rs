@@ -1,22 +1,96 @@
-use crate::{comm::CommControl, search::defs::SearchReport};
+use crate::{
+    comm::CommControl,
+    movegen::defs::Move,
+    search::{
+        defs::{BenchSummary, SearchReport},
+        Search,
+    },
+};
+
+const XBOARD: &str = "xboard";
+const CONSOLE: &str = "console";
 
 use super::Engine;
 
 impl Engine {
     pub fn search_report(&mut self, search: &SearchReport) {
         match search {
-            SearchReport::Finished(m) => {
-                self.comm.send(CommControl::BestMove(*m));
-                self.comm.send(CommControl::Update);
-            }
+            SearchReport::Finished(m, p) => match self.comm.get_protocol_name() {
+                XBOARD | CONSOLE => self.xboard_search_finished(*m, *p),
+                _ => {
+                    self.comm.send(CommControl::BestMove(*m, *p));
+                    self.comm.send(CommControl::Update);
+                }
+            },
             SearchReport::PerftScore(ps) => {
                 self.comm.send(CommControl::PerftScore(ps.clone()));
             }
+            SearchReport::BenchScore(bs) => self.bench_score(bs),
             SearchReport::SearchCurrentMove(cm) => self.comm.send(CommControl::SearchCurrMove(*cm)),
             SearchReport::SearchStats(ss) => self.comm.send(CommControl::SearchStats(*ss)),
             SearchReport::SearchSummary(sm) => {
-                self.comm.send(CommControl::SearchSummary(sm.clone()))
+                let plays_game = matches!(self.comm.get_protocol_name(), XBOARD | CONSOLE);
+                if !plays_game || self.xboard.post || self.xboard.analyze {
+                    self.comm.send(CommControl::SearchSummary(sm.clone()))
+                }
             }
         }
     }
+
+    // Under XBoard (and in the console), the engine plays the move it found
+    // on its own board, unless the search was aborted or only analyzing.
+    fn xboard_search_finished(&mut self, m: Move, p: Option<Move>) {
+        if self.xboard.discard > 0 {
+            self.xboard.discard -= 1;
+            return;
+        }
+        self.xboard.searching = false;
+
+        if self.xboard.thinking {
+            self.xboard.thinking = false;
+
+            // No move found: the game is over.
+            if m.get_move() == 0 {
+                self.send_game_result();
+                return;
+            }
+
+            let is_legal = self
+                .board
+                .lock()
+                .expect("error locking board")
+                .make_move(m, &self.movegen);
+
+            if is_legal {
+                self.xboard.hint = p;
+                self.comm.send(CommControl::BestMove(m, None));
+                self.send_game_result();
+            }
+        }
+    }
+
+    // Announce the result if the side to move is mated or stalemated.
+    fn send_game_result(&self) {
+        if let Some(result) = self.game_result() {
+            self.comm.send(CommControl::GameResult(result));
+        }
+    }
+
+    // Report the nodes searched per bench position, and the totals. The
+    // total node count is what changes when the search does.
+    fn bench_score(&self, bench: &BenchSummary) {
+        for (i, nodes) in bench.nodes.iter().enumerate() {
+            let msg = format!("bench position {} nodes {nodes}", i + 1);
+            self.comm.send(CommControl::InfoString(msg));
+        }
+
+        let nodes: usize = bench.nodes.iter().sum();
+        let nps = Search::nodes_per_second(nodes, bench.time);
+        let stopped = if bench.interrupted { " (stopped)" } else { "" };
+        let msg = format!(
+            "bench depth {} nodes {nodes} time {} nps {nps}{stopped}",
+            bench.depth, bench.time
+        );
+        self.comm.send(CommControl::InfoString(msg));
+    }
 }
@@ -46,13 +46,15 @@ impl IHashData for SearchData {
 
 impl SearchData {
     pub fn create(depth: i8, ply: i8, flag: HashFlag, value: i16, best_move: ShortMove) -> Self {
-        // Val that we store in the TT
+        // Val that we store in the TT. A mate score counts the distance to
+        // the mate from the root; in the TT it is counted from this node,
+        // because the position can be reached at another ply.
         let mut v = value;
 
         if v > CHECKMATE_THRESHOLD {
             v += ply as i16;
         }
-        if v < CHECKMATE_THRESHOLD {
+        if v < -CHECKMATE_THRESHOLD {
             v -= ply as i16;
         }
 
@@ -68,28 +70,29 @@ impl SearchData {
         // We have or don't have a value to return
         let mut value: Option<i16> = None;
 
+        // Get the val from the data. A mate score is turned back into the
+        // distance from the root, as seen from the current ply.
+        let mut v = self.value;
+
+        if v > CHECKMATE_THRESHOLD {
+            v -= ply as i16;
+        }
+        if v < -CHECKMATE_THRESHOLD {
+            v += ply as i16;
+        }
+
         if self.depth >= depth {
             match self.flag {
                 HashFlag::Exact => {
-                    // Get the val from the data.
-                    let mut v = self.value;
-
-                    if v > CHECKMATE_THRESHOLD {
-                        v += ply as i16;
-                    }
-                    if v < CHECKMATE_THRESHOLD {
-                        v -= ply as i16;
-                    }
-
                     value = Some(v);
                 }
                 HashFlag::Alpha => {
-                    if self.value <= alpha {
+                    if v <= alpha {
                         value = Some(alpha);
                     }
                 }
                 HashFlag::Beta => {
-                    if self.value >= beta {
+                    if v >= beta {
                         value = Some(beta);
                     }
                 }
@@ -217,6 +220,15 @@ impl<D: IHashData + Copy + Clone> TT<D> {
         }
     }
 
+    // How full the transposition table is, in permille.
+    pub fn hash_full(&self) -> u16 {
+        if self.megabytes > 0 {
+            ((self.used_entries as f64 / self.total_entries as f64) * 1000f64).floor() as u16
+        } else {
+            0
+        }
+    }
+
     // Clear the transposition table by resizing it to its current size.
     pub fn clear(&mut self) {
         self.resize(self.megabytes);
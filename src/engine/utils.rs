@@ -5,18 +5,24 @@ use std::error::Error;
 use crate::search::defs::{SearchControl, SearchParams, SearchType};
 use crate::{
     puzzle::Puzzle,
-    search::Search,
-    board::Board,
+    search::{helpers::MoveBook, Search},
+    board::{
+        defs::{PIECE_CHAR_SMALL, SQUARE_NAME},
+        Board,
+    },
     comm::CommControl,
-    defs::{EngineRunResult, FEN_KIWIPETE_POSITION},
+    defs::{EngineError, EngineRunResult, Sides, FEN_KIWIPETE_POSITION},
     extra::parse::{algebraic_move_to_number, PotentialMove},
     movegen::{
-        defs::{Move, MoveList, MoveType},
+        defs::{Move, MoveList, MoveType, ShortMove},
         MoveGenerator, MoveStats,
     },
 };
 
-use super::{defs::Information, Engine};
+use super::{
+    defs::{EngineOptionDefaults, Information},
+    Engine,
+};
 
 impl Engine {
     pub fn main_loop(&mut self) {
@@ -26,8 +32,21 @@ impl Engine {
         // store receiver in the Engine
         self.info_receiver = Some(info_receiver);
 
+        // start the transcript before anything is sent or received
+        let log_result = self.transport.log_to(&self.settings.log_file);
+
         // init communication
-        self.comm.init(info_sender.clone(), Arc::clone(&self.board));
+        self.comm.init(
+            info_sender.clone(),
+            Arc::clone(&self.board),
+            Arc::clone(&self.options),
+        );
+        if let Err(e) = log_result {
+            self.log_error(&e);
+        }
+
+        // load the opening book
+        self.load_book();
 
         // init search
         self.search.init(
@@ -35,7 +54,7 @@ impl Engine {
             Arc::clone(&self.board),
             Arc::clone(&self.movegen),
             Arc::clone(&self.tt_search),
-            self.settings.tt_size > 0,
+            Arc::clone(&self.book),
         );
         // update Comm interface
         self.comm.send(CommControl::Update);
@@ -61,37 +80,140 @@ impl Engine {
         }
     }
 
+    // Largest TT size (in MB) this build of the engine can allocate.
+    pub fn tt_max(&self) -> usize {
+        if std::mem::size_of::<usize>() == 8 {
+            EngineOptionDefaults::HASH_MAX_64_BIT
+        } else {
+            EngineOptionDefaults::HASH_MAX_32_BIT
+        }
+    }
+
+    // (Re)load the opening book according to the current settings. If the
+    // book is disabled or can't be read, the engine plays without one.
+    pub fn load_book(&mut self) {
+        let mut book = MoveBook::new();
+
+        if self.settings.own_book {
+            match Search::load_book(&self.settings.book_file) {
+                Ok(b) => book = b,
+                Err(e) => self.comm.send(CommControl::Error(e)),
+            }
+        }
+
+        *self.book.lock().expect("error locking book") = Arc::new(book);
+    }
+
+    // Write the transcript of the session to the file in the settings,
+    // or stop writing it if the file name is empty.
+    pub fn set_log_file(&mut self, file: &str) {
+        // GUIs send "<empty>" to clear a string option.
+        let file = if file == "<empty>" { "" } else { file };
+        self.settings.log_file = file.to_string();
+        if let Err(e) = self.transport.log_to(file) {
+            self.log_error(&e);
+        }
+    }
+
+    fn log_error(&self, e: &std::io::Error) {
+        let error = EngineError::io(&self.settings.log_file, e);
+        self.comm.send(CommControl::Error(error));
+    }
+
+    // Extra information for the GUI, sent only in debug mode.
+    pub fn debug(&self, msg: String) {
+        if self.settings.debug {
+            self.comm.send(CommControl::InfoString(msg));
+        }
+    }
+
+    // The result of the game in XBoard format, if the side to move has no
+    // legal moves left.
+    pub fn game_result(&self) -> Option<String> {
+        let mut board = self.board.lock().expect("error locking board");
+        if !self.movegen.legal_moves(&mut board).is_empty() {
+            return None;
+        }
+
+        let side = board.side_to_move();
+        let king = board.king_square(side);
+        let in_check = self
+            .movegen
+            .square_attacked(&board, board.side_to_not_move(), king);
+
+        let result = match (in_check, side) {
+            (false, _) => "1/2-1/2 {Stalemate}",
+            (true, Sides::WHITE) => "0-1 {Black mates}",
+            (true, _) => "1-0 {White mates}",
+        };
+        Some(result.to_string())
+    }
+
     pub fn setup_position(&mut self) -> EngineRunResult {
         let fen = &self.cmdline.fen()[..];
         let k = self.cmdline.has_kiwipete();
 
         let new_fen = if k { FEN_KIWIPETE_POSITION } else { &fen };
 
-        let _ = self
-            .board
+        self.board
             .lock()
             .expect("error locking board to setup fen string")
-            .read_fen(Some(&new_fen));
+            .read_fen(Some(&new_fen))?;
 
         Ok(())
     }
 
-    pub fn execute_move(&mut self, m: String) -> bool {
-        let empty = (0, 0, 0);
+    pub fn execute_move(&mut self, m: String) -> Result<(), EngineError> {
+        let potential_move = algebraic_move_to_number(&m[..])?;
+        let ips = self.psuedo_legal(potential_move, &self.board, &self.movegen)?;
 
-        let potential_move = algebraic_move_to_number(&m[..]).unwrap_or(empty);
-        let is_psuedo_legal = self.psuedo_legal(potential_move, &self.board, &self.movegen);
+        let is_legal = self
+            .board
+            .lock()
+            .expect("error locking board")
+            .make_move(ips, &self.movegen);
 
-        let mut is_legal = false;
+        if is_legal {
+            Ok(())
+        } else {
+            Err(EngineError::IllegalMove(m))
+        }
+    }
+
+    // Convert the moves given to "go searchmoves" into moves. Moves that
+    // are not legal in the current position are reported and ignored. If
+    // none of them is legal, this is an error: an empty list would let the
+    // search play any move.
+    pub fn search_moves(&self, moves: &[String]) -> Result<Vec<ShortMove>, EngineError> {
+        let mut search_moves = Vec::new();
+
+        for m in moves.iter() {
+            let pseudo_legal = algebraic_move_to_number(&m[..])
+                .and_then(|pm| self.psuedo_legal(pm, &self.board, &self.movegen));
+            let is_legal = match pseudo_legal {
+                Ok(pl) => {
+                    // Try the move on a copy of the board to see if it's legal.
+                    let mut board = self.board.lock().expect("error locking board").clone();
+                    board.make_move(pl, &self.movegen).then_some(pl)
+                }
+                Err(_) => None,
+            };
 
-        if let Ok(ips) = is_psuedo_legal {
-            is_legal = self
-                .board
-                .lock()
-                .expect("error locking board")
-                .make_move(ips, &self.movegen);
+            match is_legal {
+                Some(legal) => search_moves.push(legal.to_short_move()),
+                None => {
+                    let msg = format!("{m}: illegal move in searchmoves");
+                    self.comm.send(CommControl::InfoString(msg));
+                }
+            }
         }
-        is_legal
+
+        if !moves.is_empty() && search_moves.is_empty() {
+            let msg = String::from("no legal move in searchmoves");
+            return Err(EngineError::Protocol(msg));
+        }
+
+        Ok(search_moves)
     }
 
     pub fn psuedo_legal(
@@ -99,12 +221,14 @@ impl Engine {
         pm: PotentialMove,
         board: &Mutex<Board>,
         movegen: &MoveGenerator,
-    ) -> Result<Move, ()> {
-        let mut result = Err(());
+    ) -> Result<Move, EngineError> {
+        let name = format!("{}{}{}", SQUARE_NAME[pm.0], SQUARE_NAME[pm.1], PIECE_CHAR_SMALL[pm.2]);
+        let mut result = Err(EngineError::IllegalMove(name));
         let mut movelist = MoveList::new();
         let mut move_stats = MoveStats::new();
 
         let mutex_board = board.lock().expect("error locking board");
+        let chess960 = mutex_board.chess960;
 
         movegen.generate_moves(&mutex_board, &mut movelist, MoveType::All, &mut move_stats);
         // we dont need that sheit anymore
@@ -115,7 +239,7 @@ impl Engine {
         for i in 0..movelist.len() {
             let current = movelist.get_move(i);
             if pm.0 == current.from() {
-                if pm.1 == current.to() {
+                if pm.1 == current.notation_to(chess960) {
                     if pm.2 == current.promoted() {
                         result = Ok(current);
                         break;
@@ -127,10 +251,10 @@ impl Engine {
     }
 
     /// Function to solve puzzles and log results to a file
-    pub fn solve_puzzle(&mut self, puzzle: Puzzle,  sp: SearchParams) -> Result<(), ()> {
+    pub fn solve_puzzle(&mut self, puzzle: Puzzle,  sp: SearchParams) -> EngineRunResult {
         
         // Set up the board with the initial FEN position
-        self.board.lock().expect("error locking board").read_fen(Some(&puzzle.fen));
+        self.board.lock().expect("error locking board").read_fen(Some(&puzzle.fen))?;
 
         self.search.send(SearchControl::Start(sp, SearchType::Search));
 
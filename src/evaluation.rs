@@ -5,6 +5,7 @@ use crate::{board::Board, defs::Sides};
 pub mod defs;
 pub mod material;
 pub mod psqt;
+pub mod wdl;
 
 pub fn evaluate_position(board: &Board) -> i16 {
     //const KING_ONLY: i16 = 20; // PSQT-points
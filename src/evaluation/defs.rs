@@ -1 +1,5 @@
 pub const PIECE_VALUES: [u16; 6] = [0, 900, 500, 320, 310, 100];
+
+// The evaluation returns its score in pawns. Code that needs a finer scale
+// works in centipawns, and converts with this.
+pub const CENTIPAWNS_PER_UNIT: i32 = 100;
@@ -0,0 +1,67 @@
+use super::defs::{CENTIPAWNS_PER_UNIT, PIECE_VALUES};
+use crate::{
+    board::defs::Pieces,
+    defs::Sides,
+    search::defs::{CHECKMATE, CHECKMATE_THRESHOLD},
+};
+use serde::Serialize;
+
+// Material of both sides together in the starting position.
+const START_MATERIAL: f64 = (2
+    * (8 * PIECE_VALUES[Pieces::PAWN]
+        + 2 * PIECE_VALUES[Pieces::KNIGHT]
+        + 2 * PIECE_VALUES[Pieces::BISHOP]
+        + 2 * PIECE_VALUES[Pieces::ROOK]
+        + PIECE_VALUES[Pieces::QUEEN])) as f64;
+
+// Score (in centipawns) that gives a 50% chance of winning, and how fast
+// that chance changes with the score, in the endgame and the opening. An
+// advantage is easier to convert when there's less material on the board.
+const WIN_HALF_ENDGAME: f64 = 200.0;
+const WIN_HALF_OPENING: f64 = 350.0;
+const SPREAD_ENDGAME: f64 = 60.0;
+const SPREAD_OPENING: f64 = 100.0;
+
+// Win, draw and loss chances in permille, from the viewpoint of the side
+// the score belongs to. The three always add up to 1000.
+#[derive(PartialEq, Copy, Clone, Debug, Serialize)]
+pub struct Wdl {
+    pub win: u16,
+    pub draw: u16,
+    pub loss: u16,
+}
+
+// Estimate the WDL chances for a search score, using the material that is
+// left on the board (gamestate.material) to determine the game phase.
+pub fn wdl(score: i16, material: [u16; Sides::BOTH]) -> Wdl {
+    // A mate found by the search decides the game.
+    if score.abs() >= CHECKMATE_THRESHOLD && score.abs() <= CHECKMATE {
+        let win = if score > 0 { 1000 } else { 0 };
+        return Wdl {
+            win,
+            draw: 0,
+            loss: 1000 - win,
+        };
+    }
+
+    let total = (material[Sides::WHITE] + material[Sides::BLACK]) as f64;
+    let phase = (total / START_MATERIAL).clamp(0.0, 1.0);
+    let cp = (score as i32 * CENTIPAWNS_PER_UNIT) as f64;
+
+    let win = win_rate(cp, phase);
+    let loss = win_rate(-cp, phase);
+
+    Wdl {
+        win,
+        draw: 1000 - win - loss,
+        loss,
+    }
+}
+
+// Logistic model of the chance to win (in permille) with the given score.
+fn win_rate(cp: f64, phase: f64) -> u16 {
+    let a = WIN_HALF_ENDGAME + (WIN_HALF_OPENING - WIN_HALF_ENDGAME) * phase;
+    let b = SPREAD_ENDGAME + (SPREAD_OPENING - SPREAD_ENDGAME) * phase;
+
+    (1000.0 / (1.0 + ((a - cp) / b).exp())).round() as u16
+}
@@ -1,6 +1,9 @@
 pub mod bits;
 pub mod cmdline;
+pub mod epd;
 pub mod magics;
 pub mod parse;
+pub mod pgn;
 pub mod print;
+pub mod san;
 
@@ -22,9 +22,18 @@ impl CmdLineArgs {
     const COMM_LONG: &'static str = "comm";
     const COMM_SHORT: char = 'c';
     const COMM_HELP: &'static str = "Select communication protocol to use";
-    const COMM_VALUES: [&'static str; 2] = ["uci", "xboard"];
+    const COMM_VALUES: [&'static str; 4] = ["uci", "xboard", "json", "console"];
     const COMM_DEFAULT: &'static str = "uci";
 
+    // Serve the protocol over TCP instead of the console.
+    const LISTEN_LONG: &'static str = "listen";
+    const LISTEN_SHORT: char = 'l';
+    const LISTEN_HELP: &'static str = "Serve on this local TCP port, one session per connection";
+
+    // Transcript of everything sent and received.
+    const LOG_LONG: &'static str = "log";
+    const LOG_HELP: &'static str = "Write a transcript of the session to this file";
+
     // Threads
     const THREADS_LONG: &'static str = "threads";
     const THREADS_SHORT: char = 't';
@@ -55,6 +64,13 @@ impl CmdLineArgs {
     const EPD_TEST_LONG: &'static str = "epdtest";
     const EPD_TEST_SHORT: char = 'e';
     const EPD_TEST_HELP: &'static str = "Run EPD Test Suite";
+
+    const EPD_TIME_LONG: &'static str = "epdtime";
+    const EPD_TIME_HELP: &'static str = "Milliseconds to search each EPD position";
+    const EPD_TIME_DEFAULT: u128 = 1_000;
+
+    const EPD_DEPTH_LONG: &'static str = "epddepth";
+    const EPD_DEPTH_HELP: &'static str = "Maximum depth to search each EPD position";
 }
 
 #[derive(Debug, Clone)]
@@ -76,6 +92,19 @@ impl Cmdline {
             .clone()
     }
 
+    pub fn listen(&self) -> Option<u16> {
+        self.arguments
+            .get_one::<u16>(CmdLineArgs::LISTEN_LONG)
+            .copied()
+    }
+
+    pub fn log(&self) -> String {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::LOG_LONG)
+            .cloned()
+            .unwrap_or_default()
+    }
+
     pub fn fen(&self) -> String {
         self.arguments
             .get_one::<String>(CmdLineArgs::FEN_LONG)
@@ -112,6 +141,33 @@ impl Cmdline {
         self.arguments.get_flag(CmdLineArgs::QUIET_LONG)
     }
 
+    // The EPD options only exist if the engine is built with the "extra"
+    // feature. Otherwise, they are never set.
+    pub fn epd_test(&self) -> Option<String> {
+        self.arguments
+            .try_get_one::<String>(CmdLineArgs::EPD_TEST_LONG)
+            .ok()
+            .flatten()
+            .cloned()
+    }
+
+    pub fn epd_time(&self) -> u128 {
+        self.arguments
+            .try_get_one::<u128>(CmdLineArgs::EPD_TIME_LONG)
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(CmdLineArgs::EPD_TIME_DEFAULT)
+    }
+
+    pub fn epd_depth(&self) -> Option<i8> {
+        self.arguments
+            .try_get_one::<i8>(CmdLineArgs::EPD_DEPTH_LONG)
+            .ok()
+            .flatten()
+            .copied()
+    }
+
     fn get() -> ArgMatches {
         let mut cmd_line = clap::Command::new(About::ENGINE)
             .version(About::VERSION)
@@ -125,6 +181,21 @@ impl Cmdline {
                     .default_value(CmdLineArgs::COMM_DEFAULT)
                     .value_parser(CmdLineArgs::COMM_VALUES),
             )
+            .arg(
+                Arg::new(CmdLineArgs::LISTEN_LONG)
+                    .short(CmdLineArgs::LISTEN_SHORT)
+                    .long(CmdLineArgs::LISTEN_LONG)
+                    .help(CmdLineArgs::LISTEN_HELP)
+                    .value_parser(value_parser!(u16))
+                    .num_args(1),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::LOG_LONG)
+                    .long(CmdLineArgs::LOG_LONG)
+                    .help(CmdLineArgs::LOG_HELP)
+                    .value_parser(value_parser!(String))
+                    .num_args(1),
+            )
             .arg(
                 Arg::new(CmdLineArgs::FEN_LONG)
                     .short(CmdLineArgs::FEN_SHORT)
@@ -187,7 +258,22 @@ impl Cmdline {
                         .short(CmdLineArgs::EPD_TEST_SHORT)
                         .long(CmdLineArgs::EPD_TEST_LONG)
                         .help(CmdLineArgs::EPD_TEST_HELP)
-                        .action(ArgAction::SetTrue),
+                        .value_parser(value_parser!(String))
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new(CmdLineArgs::EPD_TIME_LONG)
+                        .long(CmdLineArgs::EPD_TIME_LONG)
+                        .help(CmdLineArgs::EPD_TIME_HELP)
+                        .value_parser(value_parser!(u128))
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new(CmdLineArgs::EPD_DEPTH_LONG)
+                        .long(CmdLineArgs::EPD_DEPTH_LONG)
+                        .help(CmdLineArgs::EPD_DEPTH_HELP)
+                        .value_parser(value_parser!(i8))
+                        .num_args(1),
                 );
         }
 
@@ -0,0 +1,241 @@
+use std::fmt;
+
+use crate::{
+    board::Board,
+    movegen::{defs::Move, MoveGenerator},
+};
+
+use super::san::san_to_move;
+
+#[derive(Debug, PartialEq)]
+pub enum EpdError {
+    Position(String),  // Fewer than four position fields.
+    Operation(String), // Operation that can't be read.
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::Position(line) => write!(f, "no position in: {line}"),
+            EpdError::Operation(op) => write!(f, "invalid operation: {op}"),
+        }
+    }
+}
+
+// One line of an EPD file: a position (a FEN without the move counters),
+// followed by operations such as:
+//
+// bm Qg6; id "WAC.001"; c0 "Mate threat";
+//
+// An operation is an opcode followed by its operands, and ends with a
+// semicolon. Operands containing spaces are quoted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epd {
+    pub position: String,                       // First four fields of a FEN.
+    pub operations: Vec<(String, Vec<String>)>, // Opcode and operands.
+}
+
+impl Epd {
+    pub fn parse(line: &str) -> Result<Self, EpdError> {
+        let mut rest = line.trim();
+        let mut position = Vec::new();
+        while position.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            position.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if position.len() < 4 {
+            return Err(EpdError::Position(line.trim().to_string()));
+        }
+
+        let mut epd = Self {
+            position: position.join(" "),
+            operations: Vec::new(),
+        };
+
+        // Some files have the move counters of a FEN after the position.
+        for _ in 0..2 {
+            if let Some((number, tail)) = rest.split_once(char::is_whitespace) {
+                if number.parse::<u16>().is_ok() {
+                    rest = tail.trim_start();
+                }
+            }
+        }
+
+        let mut chars = rest.chars().peekable();
+        loop {
+            // Skip to the opcode.
+            while chars.next_if(|c| c.is_whitespace() || *c == ';').is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let mut opcode = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                opcode.push(c);
+            }
+
+            // Read operands up to the semicolon. It is often left out at
+            // the end of the line.
+            let mut operands = Vec::new();
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.next() {
+                    None | Some(';') => break,
+                    Some('"') => {
+                        let mut operand = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some(c) => operand.push(c),
+                                None => return Err(EpdError::Operation(opcode)),
+                            }
+                        }
+                        operands.push(operand);
+                    }
+                    Some(c) => {
+                        let mut operand = String::from(c);
+                        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                            operand.push(c);
+                        }
+                        operands.push(operand);
+                    }
+                }
+            }
+
+            epd.operations.push((opcode, operands));
+        }
+
+        Ok(epd)
+    }
+
+    // The position as a complete FEN. EPD doesn't keep the move counters;
+    // the "hmvc" and "fmvn" operations may give them.
+    pub fn fen(&self) -> String {
+        let hmvc = self.operand("hmvc").unwrap_or("0");
+        let fmvn = self.operand("fmvn").unwrap_or("1");
+        format!("{} {hmvc} {fmvn}", self.position)
+    }
+
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(o, _)| o == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    // The first operand of an operation.
+    pub fn operand(&self, opcode: &str) -> Option<&str> {
+        self.operands(opcode)?.first().map(String::as_str)
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operand("id")
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.operand("c0")
+    }
+
+    // Best moves: playing any of them solves the position.
+    pub fn best_moves(&self, board: &mut Board, mg: &MoveGenerator) -> Vec<Move> {
+        self.moves("bm", board, mg)
+    }
+
+    // Moves to avoid: the position is solved by playing any other move.
+    pub fn avoid_moves(&self, board: &mut Board, mg: &MoveGenerator) -> Vec<Move> {
+        self.moves("am", board, mg)
+    }
+
+    // Direct mate: the side to move mates in this number of moves.
+    pub fn direct_mate(&self) -> Option<u8> {
+        self.operand("dm")?.parse().ok()
+    }
+
+    // Analysis count depth: the depth the position should be searched to.
+    pub fn depth(&self) -> Option<i8> {
+        self.operand("acd")?.parse().ok()
+    }
+
+    // Moves are written in SAN. Moves that are not legal in the position
+    // are left out.
+    fn moves(&self, opcode: &str, board: &mut Board, mg: &MoveGenerator) -> Vec<Move> {
+        self.operands(opcode)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|san| san_to_move(board, mg, san))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Epd, EpdError};
+    use crate::{board::Board, movegen::MoveGenerator};
+
+    const WAC_001: &str =
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    // The moves of an operation, in coordinate notation.
+    fn moves(epd: &Epd, opcode: &str) -> Vec<String> {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.read_fen(Some(&epd.fen())).expect("valid FEN");
+        let moves = match opcode {
+            "bm" => epd.best_moves(&mut board, &mg),
+            _ => epd.avoid_moves(&mut board, &mg),
+        };
+        moves.iter().map(|m| m.as_notation(false)).collect()
+    }
+
+    #[test]
+    fn reads_operations() {
+        let epd = Epd::parse(WAC_001).expect("valid EPD");
+        assert_eq!(
+            epd.position,
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -"
+        );
+        assert_eq!(epd.operations.len(), 2);
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.comment(), None);
+        assert_eq!(epd.fen(), format!("{} 0 1", epd.position));
+        assert_eq!(moves(&epd, "bm"), ["g3g6"]);
+    }
+
+    #[test]
+    fn reads_opcodes() {
+        let line = "4k3/8/8/8/8/8/8/R3K3 w Q - bm Ra8+ Kd2 Zz9; am O-O-O; dm 1; acd 12; \
+                    hmvc 7; fmvn 40; c0 \"Mate; in one\"; noop";
+        let epd = Epd::parse(line).expect("valid EPD");
+        assert_eq!(moves(&epd, "bm"), ["a1a8", "e1d2"]);
+        assert_eq!(moves(&epd, "am"), ["e1c1"]);
+        assert_eq!(epd.direct_mate(), Some(1));
+        assert_eq!(epd.depth(), Some(12));
+        assert_eq!(epd.fen(), "4k3/8/8/8/8/8/8/R3K3 w Q - 7 40");
+        assert_eq!(epd.comment(), Some("Mate; in one"));
+        assert_eq!(epd.operands("noop"), Some(&[][..]));
+        assert_eq!(epd.operands("pv"), None);
+    }
+
+    #[test]
+    fn skips_move_counters() {
+        let line = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1 bm Qg6";
+        let epd = Epd::parse(line).expect("valid EPD");
+        assert_eq!(
+            epd.operations,
+            [("bm".to_string(), vec!["Qg6".to_string()])]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert_eq!(
+            Epd::parse("8/8/8 w -"),
+            Err(EpdError::Position("8/8/8 w -".to_string()))
+        );
+        assert_eq!(
+            Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"open"),
+            Err(EpdError::Operation("id".to_string()))
+        );
+    }
+}
@@ -1,15 +1,16 @@
 use crate::board::defs::{Pieces, SQUARE_NAME};
-use crate::defs::{Piece, Square};
+use crate::defs::{EngineError, Piece, Square};
 
 pub type PotentialMove = (Square, Square, Piece);
-pub type ParseMoveResult = Result<PotentialMove, ()>;
+pub type ParseMoveResult = Result<PotentialMove, EngineError>;
 
 pub fn algebraic_move_to_number(m: &str) -> ParseMoveResult {
     let lower_case_move = m.to_ascii_lowercase();
     let mut potential_move: PotentialMove = (0, 0, Pieces::NONE);
 
     // Assume parsing the move will fail.
-    let mut parse_move_result: ParseMoveResult = Err(());
+    let invalid = || EngineError::InvalidMove(m.to_string());
+    let mut parse_move_result: ParseMoveResult = Err(invalid());
 
     // Get the "from" and "to" squares from the move stirng.
     if m.len() == 4 || m.len() == 5 {
@@ -27,9 +28,9 @@ pub fn algebraic_move_to_number(m: &str) -> ParseMoveResult {
     }
 
     // If Ok and there are 5 characters, keep parsing...
-    if parse_move_result != Err(()) && m.len() == 5 {
+    if parse_move_result.is_ok() && m.len() == 5 {
         // Again, assume that parsing will fail.
-        parse_move_result = Err(());
+        parse_move_result = Err(invalid());
 
         // Get the promotion piece character.
         let c = lower_case_move.chars().last().unwrap_or('-');
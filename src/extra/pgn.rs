@@ -0,0 +1,759 @@
+use std::{
+    fmt,
+    io::{self, BufRead},
+    iter::Peekable,
+    str::Chars,
+};
+
+use crate::{
+    board::{Board, FenError},
+    defs::{Sides, FEN_START_POSITION, MAX_GAME_MOVES},
+    movegen::{defs::Move, MoveGenerator},
+};
+
+use super::san::{move_to_san, san_to_move};
+
+// The tags every game has, in the order in which they are written.
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+const UNKNOWN_RESULT: &str = "*";
+const LINE_LENGTH: usize = 80; // Maximum length of a movetext line.
+
+// Move annotations written as a suffix, and the NAG each one stands for.
+const SUFFIX_NAGS: [(&str, u8); 6] = [
+    ("!", 1),
+    ("?", 2),
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+];
+
+#[derive(Debug)]
+pub enum PgnError {
+    Io(io::Error),
+    Syntax(String),        // Movetext or tag that can't be read.
+    Fen(String, FenError), // The FEN tag holds an invalid position.
+    IllegalMove(String),   // Move of the main line that can't be played.
+    TooLong,               // More moves than the board can keep.
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Io(e) => write!(f, "{e}"),
+            PgnError::Syntax(s) => write!(f, "syntax error: {s}"),
+            PgnError::Fen(fen, e) => write!(f, "invalid FEN tag: {fen}: {e}"),
+            PgnError::IllegalMove(m) => write!(f, "illegal move: {m}"),
+            PgnError::TooLong => write!(f, "game is too long"),
+        }
+    }
+}
+
+impl From<io::Error> for PgnError {
+    fn from(e: io::Error) -> Self {
+        PgnError::Io(e)
+    }
+}
+
+// A move in SAN, with everything written about it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>,                  // Numeric Annotation Glyphs ($1 = "!")
+    pub comment_before: Option<String>, // Comment in front of the move.
+    pub comment: Option<String>,        // Comment after the move.
+    pub variations: Vec<Vec<PgnMove>>,  // Lines played instead of this move.
+}
+
+impl PgnMove {
+    pub fn new(san: String) -> Self {
+        Self {
+            san,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>, // In the order they were read.
+    pub moves: Vec<PgnMove>,         // The main line.
+    pub result: String,
+}
+
+impl PgnGame {
+    // Create the record of a game played from the given position. The
+    // moves must be legal. Tags can be added with set_tag().
+    pub fn from_moves(start: &Board, moves: &[Move], mg: &MoveGenerator) -> Self {
+        let mut game = Self {
+            result: String::from(UNKNOWN_RESULT),
+            ..Default::default()
+        };
+
+        for tag in SEVEN_TAG_ROSTER {
+            game.set_tag(tag, "?");
+        }
+        game.set_tag("Result", UNKNOWN_RESULT);
+
+        let fen = start.create_fen();
+        if fen != FEN_START_POSITION {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+
+        let mut board = start.clone();
+        for &m in moves {
+            game.moves
+                .push(PgnMove::new(move_to_san(&mut board, mg, m)));
+            board.make_move(m, mg);
+        }
+
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+
+        if name == "Result" {
+            self.result = value.to_string();
+        }
+    }
+
+    // The position the game starts from: the FEN tag, if there is one.
+    pub fn start_position(&self) -> Result<Board, PgnError> {
+        let mut board = Board::new();
+        let fen = self.tag("FEN").unwrap_or(FEN_START_POSITION);
+        match board.read_fen(Some(fen)) {
+            Ok(()) => Ok(board),
+            Err(e) => Err(PgnError::Fen(fen.to_string(), e)),
+        }
+    }
+
+    // Play the main line. Returns the final position, with the game in its
+    // history, and the moves that were played.
+    pub fn replay(&self, mg: &MoveGenerator) -> Result<(Board, Vec<Move>), PgnError> {
+        let mut board = self.start_position()?;
+        let mut moves = Vec::new();
+
+        for pgn_move in self.moves.iter() {
+            if board.history.len() >= MAX_GAME_MOVES - 1 {
+                return Err(PgnError::TooLong);
+            }
+
+            let (number, white) = move_number(&board);
+            let m = san_to_move(&mut board, mg, &pgn_move.san).ok_or_else(|| {
+                let dots = if white { "." } else { "..." };
+                PgnError::IllegalMove(format!("{number}{dots} {}", pgn_move.san))
+            })?;
+
+            board.make_move(m, mg);
+            moves.push(m);
+        }
+
+        Ok((board, moves))
+    }
+}
+
+// Write the game in export format: the Seven Tag Roster first, the other
+// tags after it, and the movetext in lines of at most 80 characters.
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self.result.as_str() {
+            "" => UNKNOWN_RESULT,
+            r => r,
+        };
+
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => result,
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            writeln!(f, "[{name} \"{}\"]", escape(value))?;
+        }
+        for (name, value) in self.tags.iter() {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                writeln!(f, "[{name} \"{}\"]", escape(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        // Start numbering the moves as the position in the FEN tag says.
+        let (number, white) = match self.start_position() {
+            Ok(board) => move_number(&board),
+            Err(_) => (1, true),
+        };
+
+        let mut tokens = Vec::new();
+        write_line(&self.moves, number, white, &mut tokens);
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_LENGTH {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+// Reads the games in a PGN file one at a time, so files with millions of
+// games can be processed without loading them into memory.
+pub struct PgnReader<R: BufRead> {
+    input: R,
+    next_line: Option<String>, // Line read ahead: the first of the next game.
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            next_line: None,
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        if let Some(line) = self.next_line.take() {
+            return Ok(Some(line));
+        }
+
+        let mut line = String::new();
+        match self.input.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    // Collect the tag lines and movetext of the next game. The movetext
+    // ends at an empty line (outside of a comment), or where the tags of
+    // the next game start.
+    fn read_game_text(&mut self) -> io::Result<Option<(Vec<String>, String)>> {
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        let mut in_comment = false;
+
+        while let Some(line) = self.read_line()? {
+            let trimmed = line.trim();
+
+            if trimmed.starts_with('[') && !in_comment {
+                if !movetext.trim().is_empty() {
+                    self.next_line = Some(line);
+                    break;
+                }
+                tags.push(trimmed.to_string());
+            } else if trimmed.is_empty() && !in_comment {
+                if !movetext.trim().is_empty() {
+                    break;
+                }
+            } else {
+                in_comment = comment_open_after(&line, in_comment);
+                movetext.push_str(&line);
+                if !line.ends_with('\n') {
+                    movetext.push('\n');
+                }
+            }
+        }
+
+        if tags.is_empty() && movetext.trim().is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some((tags, movetext)))
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (tags, movetext) = match self.read_game_text() {
+            Ok(Some(text)) => text,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(PgnError::Io(e))),
+        };
+
+        Some(parse_game(&tags, &movetext))
+    }
+}
+
+fn parse_game(tag_lines: &[String], movetext: &str) -> Result<PgnGame, PgnError> {
+    let mut game = PgnGame::default();
+
+    for line in tag_lines {
+        let (name, value) = parse_tag(line)?;
+        game.set_tag(&name, &value);
+    }
+
+    // The lines being read: the main line, and the variations inside it.
+    let mut lines: Vec<Vec<PgnMove>> = vec![Vec::new()];
+    let mut comment_before: Option<String> = None;
+    let mut result: Option<String> = None;
+
+    for token in Tokens::new(movetext) {
+        let line = lines.last_mut().expect("main line is always there");
+
+        match token {
+            Token::Move(san, nags) => {
+                let mut pgn_move = PgnMove::new(san);
+                pgn_move.nags = nags;
+                pgn_move.comment_before = comment_before.take();
+                line.push(pgn_move);
+            }
+            Token::Nag(nag) => match line.last_mut() {
+                Some(m) => m.nags.push(nag),
+                None => return Err(syntax_error("annotation without a move")),
+            },
+            Token::Comment(text) => match line.last_mut() {
+                Some(m) if comment_before.is_none() => add_comment(&mut m.comment, text),
+                _ => add_comment(&mut comment_before, text),
+            },
+            Token::VariationStart => {
+                if line.is_empty() {
+                    return Err(syntax_error("variation without a move"));
+                }
+                lines.push(Vec::new());
+            }
+            Token::VariationEnd => {
+                if lines.len() < 2 {
+                    return Err(syntax_error("')' without '('"));
+                }
+                let variation = lines.pop().expect("checked above");
+                let parent = lines.last_mut().expect("checked above");
+                let m = parent.last_mut().expect("checked at '('");
+                m.variations.push(variation);
+            }
+            Token::Result(r) => {
+                result = Some(r);
+                break;
+            }
+        }
+    }
+
+    if lines.len() > 1 {
+        return Err(syntax_error("variation is not closed"));
+    }
+
+    game.moves = lines.pop().unwrap_or_default();
+
+    // The result at the end of the movetext is the one that counts, but a
+    // game without one still has its tag.
+    game.result = result
+        .or_else(|| game.tag("Result").map(String::from))
+        .unwrap_or_else(|| String::from(UNKNOWN_RESULT));
+
+    Ok(game)
+}
+
+// [Name "Value"], where the value may contain \" and \\.
+fn parse_tag(line: &str) -> Result<(String, String), PgnError> {
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|l| l.strip_suffix(']'))
+        .ok_or_else(|| syntax_error(line))?;
+    let (name, value) = inner
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| syntax_error(line))?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| syntax_error(line))?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c),
+        }
+    }
+
+    Ok((name.to_string(), unescaped))
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn syntax_error(s: &str) -> PgnError {
+    PgnError::Syntax(s.to_string())
+}
+
+fn add_comment(comment: &mut Option<String>, text: String) {
+    *comment = Some(match comment.take() {
+        Some(c) => format!("{c} {text}"),
+        None => text,
+    });
+}
+
+// Whether a {} comment is still open at the end of a line of movetext,
+// by the same rules as the tokenizer: a '{' in a ';' comment, or on a
+// line escaped with '%', doesn't start a comment.
+fn comment_open_after(line: &str, mut in_comment: bool) -> bool {
+    if !in_comment && line.starts_with('%') {
+        return false;
+    }
+
+    for c in line.chars() {
+        match c {
+            '{' if !in_comment => in_comment = true,
+            '}' if in_comment => in_comment = false,
+            ';' if !in_comment => break,
+            _ => (),
+        }
+    }
+
+    in_comment
+}
+
+// The number of the move to be played, and if it is White's move.
+fn move_number(board: &Board) -> (u16, bool) {
+    let white = board.side_to_move() == Sides::WHITE;
+    (board.gamestate.fullmove_number, white)
+}
+
+// Add the tokens for a line of moves, starting at the given move number,
+// with its comments and variations. A move is kept together with its
+// number, and parentheses with the first and last token of a variation.
+fn write_line(moves: &[PgnMove], mut number: u16, mut white: bool, tokens: &mut Vec<String>) {
+    // Black's move needs its number if something was written in between.
+    let mut needs_number = true;
+
+    for m in moves {
+        if let Some(c) = &m.comment_before {
+            tokens.push(format!("{{{c}}}"));
+            needs_number = true;
+        }
+
+        let san = &m.san;
+        tokens.push(match (white, needs_number) {
+            (true, _) => format!("{number}. {san}"),
+            (false, true) => format!("{number}... {san}"),
+            (false, false) => san.clone(),
+        });
+        tokens.extend(m.nags.iter().map(|nag| format!("${nag}")));
+        needs_number = false;
+
+        if let Some(c) = &m.comment {
+            tokens.push(format!("{{{c}}}"));
+            needs_number = true;
+        }
+
+        for variation in m.variations.iter() {
+            let mut variation_tokens = Vec::new();
+            write_line(variation, number, white, &mut variation_tokens);
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+            }
+            tokens.extend(variation_tokens);
+            needs_number = true;
+        }
+
+        if !white {
+            number += 1;
+        }
+        white = !white;
+    }
+}
+
+enum Token {
+    Move(String, Vec<u8>), // Move in SAN, with the NAGs of its suffix.
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+// Splits movetext into tokens. Move numbers are skipped, as they follow
+// from the moves.
+struct Tokens<'a> {
+    chars: Peekable<Chars<'a>>,
+    line_start: bool, // At the start of a line, where '%' escapes it.
+}
+
+impl<'a> Tokens<'a> {
+    fn new(movetext: &'a str) -> Self {
+        Self {
+            chars: movetext.chars().peekable(),
+            line_start: true,
+        }
+    }
+
+    fn read_until(&mut self, end: char) -> String {
+        let text: String = self.chars.by_ref().take_while(|&c| c != end).collect();
+        self.line_start = end == '\n';
+        text.trim().to_string()
+    }
+
+    fn read_symbol(&mut self, first: char) -> String {
+        let mut symbol = String::from(first);
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || "{}();$".contains(c) {
+                break;
+            }
+            symbol.push(c);
+            self.chars.next();
+        }
+        symbol
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        while let Some(c) = self.chars.next() {
+            let line_start = self.line_start;
+            self.line_start = c == '\n';
+
+            let token = match c {
+                c if c.is_whitespace() => continue,
+                '%' if line_start => {
+                    self.read_until('\n');
+                    continue;
+                }
+                '{' => Token::Comment(self.read_until('}')),
+                ';' => Token::Comment(self.read_until('\n')),
+                '(' => Token::VariationStart,
+                ')' => Token::VariationEnd,
+                '$' => {
+                    let mut digits = String::new();
+                    while let Some(d) = self.chars.next_if(|d| d.is_ascii_digit()) {
+                        digits.push(d);
+                    }
+                    match digits.parse() {
+                        Ok(nag) => Token::Nag(nag),
+                        Err(_) => continue,
+                    }
+                }
+                _ => {
+                    let symbol = self.read_symbol(c);
+                    if RESULTS.contains(&symbol.as_str()) {
+                        Token::Result(symbol)
+                    } else {
+                        match split_suffix(strip_move_number(&symbol)) {
+                            Some(token) => token,
+                            None => continue,
+                        }
+                    }
+                }
+            };
+
+            return Some(token);
+        }
+
+        None
+    }
+}
+
+// Skip the move number ("12." or "12..."), also if the move is stuck to
+// it, as in "12.e4". Digits without a dot are kept: "0-0" is castling.
+fn strip_move_number(symbol: &str) -> &str {
+    let after_digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    let after_dots = after_digits.trim_start_matches('.');
+    let has_number = after_digits.len() < symbol.len() && after_dots.len() < after_digits.len();
+
+    if has_number {
+        after_dots
+    } else {
+        symbol
+    }
+}
+
+// Split "e4!?" into the move and its annotation. An annotation on its own
+// belongs to the move before it. Unknown annotations are dropped.
+fn split_suffix(san: &str) -> Option<Token> {
+    let bare = san.trim_end_matches(['!', '?']);
+    let suffix = &san[bare.len()..];
+    let nag = SUFFIX_NAGS
+        .iter()
+        .find(|(s, _)| *s == suffix)
+        .map(|(_, nag)| *nag);
+
+    match (bare.is_empty(), nag) {
+        (true, Some(nag)) => Some(Token::Nag(nag)),
+        (true, None) => None,
+        (false, _) => Some(Token::Move(bare.to_string(), nag.into_iter().collect())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PgnError, PgnGame, PgnMove, PgnReader};
+    use crate::{board::Board, extra::san::san_to_move, movegen::MoveGenerator};
+
+    const GAME: &str = r#"[Event "Test"]
+[White "A \"B\" C"]
+[Result "1-0"]
+
+{Start} 1. e4 $1 e5!? 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 {Knight} ; rest of line
+3. Bb5 ! a6 1-0
+"#;
+
+    fn read(pgn: &str) -> Vec<Result<PgnGame, PgnError>> {
+        PgnReader::new(pgn.as_bytes()).collect()
+    }
+
+    fn read_one(pgn: &str) -> Result<PgnGame, PgnError> {
+        read(pgn).into_iter().next().expect("a game")
+    }
+
+    fn sans(moves: &[PgnMove]) -> Vec<&str> {
+        moves.iter().map(|m| m.san.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_tags() {
+        let game = read_one(GAME).expect("valid game");
+        assert_eq!(game.tag("Event"), Some("Test"));
+        assert_eq!(game.tag("White"), Some("A \"B\" C"));
+        assert_eq!(game.tag("Black"), None);
+        assert_eq!(game.result, "1-0");
+    }
+
+    #[test]
+    fn reads_comments_and_nags() {
+        let game = read_one(GAME).expect("valid game");
+        let moves = &game.moves;
+        assert_eq!(sans(moves), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+
+        assert_eq!(moves[0].comment_before.as_deref(), Some("Start"));
+        assert_eq!(moves[0].nags, [1]);
+        assert_eq!(moves[1].nags, [5]);
+        assert_eq!(moves[3].comment.as_deref(), Some("Knight rest of line"));
+        assert_eq!(moves[4].nags, [1]);
+        assert!(moves[5].nags.is_empty() && moves[5].comment.is_none());
+    }
+
+    #[test]
+    fn reads_variations() {
+        let game = read_one(GAME).expect("valid game");
+        let variations = &game.moves[2].variations;
+        assert_eq!(variations.len(), 1);
+        assert_eq!(sans(&variations[0]), ["f4", "exf4", "Nf3"]);
+        assert_eq!(variations[0][1].variations.len(), 1);
+        assert_eq!(sans(&variations[0][1].variations[0]), ["d5"]);
+
+        let mg = MoveGenerator::new();
+        let (_, moves) = game.replay(&mg).expect("legal main line");
+        assert_eq!(moves.len(), 6);
+    }
+
+    #[test]
+    fn reads_castling() {
+        let pgn = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4.0-0 Nf6 5. d3 5...O-O *";
+        let game = read_one(pgn).expect("valid game");
+        assert_eq!(
+            sans(&game.moves),
+            ["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5", "0-0", "Nf6", "d3", "O-O"]
+        );
+        assert_eq!(game.result, "*");
+
+        let mg = MoveGenerator::new();
+        let (_, moves) = game.replay(&mg).expect("legal main line");
+        assert!(moves[6].castling() && moves[9].castling());
+    }
+
+    #[test]
+    fn reads_several_games() {
+        let pgn = format!("{GAME}\n% Escaped line\n1. d4 d5 1/2-1/2\n\n1. c4 *\n");
+        let games: Vec<PgnGame> = read(&pgn).into_iter().map(|g| g.expect("valid")).collect();
+        assert_eq!(games.len(), 3);
+        assert_eq!(sans(&games[1].moves), ["d4", "d5"]);
+        assert_eq!(games[1].result, "1/2-1/2");
+        assert_eq!(sans(&games[2].moves), ["c4"]);
+    }
+
+    #[test]
+    fn reads_braces_in_comments() {
+        let pgn = "1. e4 ; not a {comment\n% nor {this\n\n[Event \"Next\"]\n\n1. d4 {multi\n\nline; } *\n";
+        let games: Vec<PgnGame> = read(pgn).into_iter().map(|g| g.expect("valid")).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].tag("Event"), Some("Next"));
+        assert_eq!(games[1].moves[0].comment.as_deref(), Some("multi\n\nline;"));
+    }
+
+    #[test]
+    fn rejects_bad_movetext() {
+        for pgn in [
+            "1. e4 (1. d4",
+            "1. e4 ) e5",
+            "$1 1. e4",
+            "(1. e4) *",
+            "[Event Test]\n\n*",
+        ] {
+            assert!(matches!(read_one(pgn), Err(PgnError::Syntax(_))), "{pgn}");
+        }
+
+        let mg = MoveGenerator::new();
+        let game = read_one("1. e4 e4 *").expect("valid game");
+        match game.replay(&mg) {
+            Err(PgnError::IllegalMove(m)) => assert_eq!(m, "1... e4"),
+            _ => panic!("e4 can't be played twice"),
+        }
+
+        let game = read_one("[FEN \"invalid\"]\n\n*").expect("valid game");
+        assert!(matches!(game.replay(&mg), Err(PgnError::Fen(..))));
+    }
+
+    #[test]
+    fn writes_games() {
+        let game = read_one(GAME).expect("valid game");
+        let written = game.to_string();
+        assert!(written.starts_with("[Event \"Test\"]\n[Site \"?\"]\n"));
+        assert!(written.contains("[White \"A \\\"B\\\" C\"]\n"));
+        assert!(written.contains(
+            "{Start} 1. e4 $1 e5 $5 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6\n\
+             {Knight rest of line} 3. Bb5 $1 a6 1-0\n"
+        ));
+
+        let again = read_one(&written).expect("valid game");
+        assert_eq!(again.moves, game.moves);
+        assert_eq!(again.tag("White"), game.tag("White"));
+        assert_eq!(again.result, game.result);
+    }
+
+    #[test]
+    fn writes_moves_from_position() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board
+            .read_fen(Some("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 5"))
+            .expect("valid FEN");
+
+        let mut line = board.clone();
+        let mut moves = Vec::new();
+        for san in ["O-O-O", "0-0"] {
+            let m = san_to_move(&mut line, &mg, san).expect("legal move");
+            line.make_move(m, &mg);
+            moves.push(m);
+        }
+
+        let game = PgnGame::from_moves(&board, &moves, &mg);
+        let written = game.to_string();
+        assert!(written.contains("[SetUp \"1\"]\n"));
+        assert!(written.contains("\n5... O-O-O 6. O-O *\n"));
+        assert_eq!(game.replay(&mg).expect("legal moves").1, moves);
+    }
+}
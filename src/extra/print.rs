@@ -1,3 +1,7 @@
+use std::fmt::Write;
+
+use colored::Colorize;
+
 use crate::{
     board::{
         defs::{Pieces, SQUARE_NAME},
@@ -23,39 +27,76 @@ const CHAR_BB: char = 'b';
 const CHAR_BN: char = 'n';
 const CHAR_BP: char = 'p';
 
-pub fn print_position(board: &Board, highlight_bitmask: bool, bitmask: Option<Bitboard>) {
+// The board as ASCII art, followed by the game state. The board can also
+// show a bitmask instead of the pieces.
+pub fn position_as_string(
+    board: &Board,
+    highlight_bitmask: bool,
+    bitmask: Option<Bitboard>,
+) -> String {
     let mut ascii_board: AsciiBoard = [CHAR_ES; NrOf::SQUARES];
 
-    if !highlight_bitmask {
+    if highlight_bitmask {
+        let bitmask = bitmask.unwrap();
+        for (index, square) in ascii_board.iter_mut().enumerate() {
+            if (bitmask & (1 << index)) != 0 {
+                *square = '*';
+            }
+        }
+    } else {
         board_to_ascii(board, &mut ascii_board);
     }
 
-    // Print the board with grid lines
-    for rank in (0..8).rev() {
-        print!("\n +---+---+---+---+---+---+---+---+\n");
-        for file in 0..8 {
-            let index = rank * 8 + file;
-            let piece_char = if highlight_bitmask {
-                if (bitmask.unwrap() & (1 << index)) != 0 {
-                    '*'
-                } else {
-                    ' '
-                }
-            } else {
-                ascii_board[index]
-            };
-            print!(" | {}", piece_char);
-        }
-        if rank == 0 {
-            print!(" | {}\n", rank + 1);
+    let mut out = grid(&ascii_board, false, |c| c.to_string());
+    game_metadata(board, &mut out);
+    out
+}
+
+// The board as a player at the console sees it: from Black's side if it
+// is flipped, and with the pieces in colour if the terminal supports it.
+pub fn position_for_player(board: &Board, flipped: bool) -> String {
+    let mut ascii_board: AsciiBoard = [CHAR_ES; NrOf::SQUARES];
+    board_to_ascii(board, &mut ascii_board);
+
+    grid(&ascii_board, flipped, |c| {
+        let piece = c.to_string();
+        if c == CHAR_ES {
+            piece
+        } else if c.is_ascii_uppercase() {
+            piece.bright_white().bold().to_string()
         } else {
-            print!(" | {}", rank + 1);
+            piece.bright_red().bold().to_string()
+        }
+    })
+}
+
+// Draw the board with grid lines, with the pieces drawn by 'paint'.
+fn grid(ascii_board: &AsciiBoard, flipped: bool, paint: impl Fn(char) -> String) -> String {
+    const LINE: &str = " +---+---+---+---+---+---+---+---+\n";
+    let mut ranks: Vec<usize> = (0..8).rev().collect();
+    let mut files: Vec<usize> = (0..8).collect();
+    if flipped {
+        ranks.reverse();
+        files.reverse();
+    }
+
+    let mut out = String::new();
+    for &rank in ranks.iter() {
+        out.push('\n');
+        out.push_str(LINE);
+        for &file in files.iter() {
+            let _ = write!(out, " | {}", paint(ascii_board[rank * 8 + file]));
         }
+        let _ = write!(out, " | {}", rank + 1);
     }
-    print!(" +---+---+---+---+---+---+---+---+\n");
-    print!("   a   b   c   d   e   f   g   h\n\n");
+    out.push('\n');
+    out.push_str(LINE);
 
-    game_metadata(board);
+    for &file in files.iter() {
+        let _ = write!(out, "   {}", (b'a' + file as u8) as char);
+    }
+    out.push_str("\n\n");
+    out
 }
 
 fn board_to_ascii(board: &Board, ascii_board: &mut AsciiBoard) {
@@ -101,7 +142,7 @@ fn put_character_on_square(bitboard: Bitboard, ascii_board: &mut AsciiBoard, cha
     }
 }
 
-fn game_metadata(board: &Board) {
+fn game_metadata(board: &Board, out: &mut String) {
     let is_white = (board.gamestate.active_color as usize) == Sides::WHITE;
     let turn = if is_white { "White" } else { "Black" };
     let castling = castling_as_string(board.gamestate.castling);
@@ -115,15 +156,19 @@ fn game_metadata(board: &Board) {
     let full_movenumber = board.gamestate.fullmove_number;
 
     let eval = count(&board);
-    println!("{}{}", "Fen: ", board.create_fen());
-    println!("{:<20}{}", "White eval:", eval.0);
-    println!("{:<20}{}", "Black eval:", eval.1);
-
-    println!("{:<20}{:x}", "Zobrist key:", board.gamestate.zobrist_key);
-    println!("{:<20}{}", "Active Color:", turn);
-    println!("{:<20}{}", "Castling:", castling);
-    println!("{:<20}{}", "En Passant:", en_passant);
-    println!("{:<20}{}", "Half-move clock:", half_moveclock);
-    println!("{:<20}{}", "Full-move number:", full_movenumber);
-    println!();
+    let _ = writeln!(out, "{}{}", "Fen: ", board.create_fen());
+    let _ = writeln!(out, "{:<20}{}", "White eval:", eval.0);
+    let _ = writeln!(out, "{:<20}{}", "Black eval:", eval.1);
+
+    let _ = writeln!(
+        out,
+        "{:<20}{:x}",
+        "Zobrist key:", board.gamestate.zobrist_key
+    );
+    let _ = writeln!(out, "{:<20}{}", "Active Color:", turn);
+    let _ = writeln!(out, "{:<20}{}", "Castling:", castling);
+    let _ = writeln!(out, "{:<20}{}", "En Passant:", en_passant);
+    let _ = writeln!(out, "{:<20}{}", "Half-move clock:", half_moveclock);
+    let _ = writeln!(out, "{:<20}{}", "Full-move number:", full_movenumber);
+    out.push('\n');
 }
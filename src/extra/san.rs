@@ -0,0 +1,328 @@
+use crate::{
+    board::{
+        defs::{Pieces, Wings, PIECE_CHAR_CAPS, SQUARE_NAME},
+        Board,
+    },
+    defs::Piece,
+    movegen::{defs::Move, MoveGenerator},
+};
+
+use super::parse::{algebraic_square_to_number, promotion_piece_letter_to_number};
+
+// Write a legal move in Standard Algebraic Notation, such as "e4", "Nbd7",
+// "exd5", "e8=Q+" or "O-O". The move must be legal in the position.
+pub fn move_to_san(board: &mut Board, mg: &MoveGenerator, m: Move) -> String {
+    let mut san = if m.castling() {
+        match m.castling_wing() {
+            Wings::KING => String::from("O-O"),
+            _ => String::from("O-O-O"),
+        }
+    } else {
+        let piece = m.piece();
+        let capture = m.captured() != Pieces::NONE || m.en_passant();
+        let mut san = String::from(PIECE_CHAR_CAPS[piece]);
+
+        // A pawn capture is written with the file the pawn came from.
+        if piece == Pieces::PAWN {
+            if capture {
+                san.push_str(&SQUARE_NAME[m.from()][..1]);
+            }
+        } else {
+            san.push_str(&origin(board, mg, m));
+        }
+
+        if capture {
+            san.push('x');
+        }
+        san.push_str(SQUARE_NAME[m.to()]);
+
+        if m.promoted() != Pieces::NONE {
+            san.push('=');
+            san.push_str(PIECE_CHAR_CAPS[m.promoted()]);
+        }
+
+        san
+    };
+
+    // See if the move gives check or mate.
+    if board.make_move(m, mg) {
+        let side = board.side_to_move();
+        let king = board.king_square(side);
+        if mg.square_attacked(board, board.side_to_not_move(), king) {
+            let mate = mg.legal_moves(board).is_empty();
+            san.push(if mate { '#' } else { '+' });
+        }
+        board.unmake();
+    }
+
+    san
+}
+
+// Write a line of moves in SAN, starting from the given position. Moves
+// after one that is not legal in the position are left out.
+pub fn line_to_san(board: &Board, mg: &MoveGenerator, line: &[Move]) -> Vec<String> {
+    let mut board = board.clone();
+    let mut san = Vec::new();
+
+    for &m in line {
+        let text = move_to_san(&mut board, mg, m);
+        if !board.make_move(m, mg) {
+            break;
+        }
+        san.push(text);
+    }
+
+    san
+}
+
+// Find the legal move a move in Standard Algebraic Notation stands for,
+// such as "e4", "Nbd7", "exd5", "e8=Q+" or "O-O". Sloppy notation is also
+// understood: a missing or superfluous origin square ("Ng1f3", "e2e4"),
+// lower case promotion pieces ("e8q") and pawn letters ("Pe4"). Returns
+// None if the move is illegal or ambiguous.
+pub fn san_to_move(board: &mut Board, mg: &MoveGenerator, san: &str) -> Option<Move> {
+    // Check and mate markers and annotations don't identify the move.
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = mg.legal_moves(board);
+
+    // Castling is written the same way in standard chess and Chess960.
+    let wing = match san {
+        "O-O" | "0-0" | "o-o" => Some(Wings::KING),
+        "O-O-O" | "0-0-0" | "o-o-o" => Some(Wings::QUEEN),
+        _ => None,
+    };
+    if let Some(w) = wing {
+        return legal_moves
+            .into_iter()
+            .find(|m| m.castling() && m.castling_wing() == w);
+    }
+
+    // The piece letter; pawn moves don't have one. Without a letter, the
+    // move may also be in coordinate notation, which can move any piece.
+    let (piece, rest) = match san.chars().next() {
+        Some('K') => (Some(Pieces::KING), &san[1..]),
+        Some('Q') => (Some(Pieces::QUEEN), &san[1..]),
+        Some('R') => (Some(Pieces::ROOK), &san[1..]),
+        Some('B') => (Some(Pieces::BISHOP), &san[1..]),
+        Some('N') => (Some(Pieces::KNIGHT), &san[1..]),
+        Some('P') => (Some(Pieces::PAWN), &san[1..]),
+        _ => (None, san),
+    };
+
+    let (squares, promoted) = split_promotion(piece, rest)?;
+
+    // What's left are the squares: the destination, and before it the
+    // (part of the) origin square needed to tell two moves apart.
+    let squares: String = squares.chars().filter(|&c| c != 'x' && c != '-').collect();
+    if squares.len() < 2 || !squares.is_ascii() {
+        return None;
+    }
+    let (from, to) = squares.split_at(squares.len() - 2);
+    let to = algebraic_square_to_number(to)?;
+    let (from_file, from_rank) = disambiguation(from)?;
+
+    // Only a full origin square ("e2e4", "g1f3") can stand for a piece
+    // other than a pawn without naming it.
+    let piece = match piece {
+        Some(p) => Some(p),
+        None if from_file.is_some() && from_rank.is_some() => None,
+        None => Some(Pieces::PAWN),
+    };
+
+    let mut candidates = legal_moves.into_iter().filter(|m| {
+        let (file, rank) = Board::square_on_file_rank(m.from());
+        !m.castling()
+            && piece.is_none_or(|p| p == m.piece())
+            && m.to() == to
+            && m.promoted() == promoted
+            && from_file.is_none_or(|f| f == file)
+            && from_rank.is_none_or(|r| r == rank)
+    });
+
+    match (candidates.next(), candidates.next()) {
+        (Some(m), None) => Some(m),
+        _ => None,
+    }
+}
+
+// Split off the promotion piece of a pawn move: "e8=Q", "e8Q" or "e8q".
+// As a square always ends in a digit, a letter at the end can only be a
+// promotion piece.
+fn split_promotion(piece: Option<Piece>, san: &str) -> Option<(&str, Piece)> {
+    if piece.is_some_and(|p| p != Pieces::PAWN) {
+        return Some((san, Pieces::NONE));
+    }
+
+    match san.chars().last() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            let promoted = promotion_piece_letter_to_number(c)?;
+            let squares = san[..san.len() - 1].trim_end_matches('=');
+            Some((squares, promoted))
+        }
+        _ => Some((san, Pieces::NONE)),
+    }
+}
+
+// The part of the origin square needed to tell the move apart from moves
+// of the same kind of piece to the same square: the file if it differs,
+// otherwise the rank, and the whole square if neither is enough.
+fn origin(board: &mut Board, mg: &MoveGenerator, m: Move) -> String {
+    let from = SQUARE_NAME[m.from()];
+    let (file, rank) = Board::square_on_file_rank(m.from());
+    let others: Vec<(u8, u8)> = mg
+        .legal_moves(board)
+        .into_iter()
+        .filter(|o| o.piece() == m.piece() && o.to() == m.to() && o.from() != m.from())
+        .filter(|o| !o.castling())
+        .map(|o| Board::square_on_file_rank(o.from()))
+        .collect();
+
+    if others.is_empty() {
+        String::new()
+    } else if others.iter().all(|&(f, _)| f != file) {
+        from[..1].to_string()
+    } else if others.iter().all(|&(_, r)| r != rank) {
+        from[1..].to_string()
+    } else {
+        from.to_string()
+    }
+}
+
+// The file and/or rank of the origin square, as given in the move.
+fn disambiguation(from: &str) -> Option<(Option<u8>, Option<u8>)> {
+    let mut file = None;
+    let mut rank = None;
+
+    for c in from.chars() {
+        match c {
+            'a'..='h' if file.is_none() => file = Some(c as u8 - b'a'),
+            '1'..='8' if rank.is_none() => rank = Some(c as u8 - b'1'),
+            _ => return None,
+        }
+    }
+
+    Some((file, rank))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{move_to_san, san_to_move};
+    use crate::{
+        board::Board,
+        defs::{FEN_KIWIPETE_POSITION, FEN_START_POSITION},
+        movegen::MoveGenerator,
+    };
+
+    fn setup(fen: &str) -> (Board, MoveGenerator) {
+        let mut board = Board::new();
+        board.read_fen(Some(fen)).expect("valid FEN");
+        (board, MoveGenerator::new())
+    }
+
+    // Write the move given in coordinate notation ("e2e4", "e7e8q") in SAN.
+    fn san(fen: &str, notation: &str) -> String {
+        let (mut board, mg) = setup(fen);
+        let m = mg
+            .legal_moves(&mut board)
+            .into_iter()
+            .find(|m| m.as_notation(false) == notation)
+            .expect("legal move");
+        move_to_san(&mut board, &mg, m)
+    }
+
+    // Read a move in SAN, and write it in coordinate notation.
+    fn parse(fen: &str, san: &str) -> Option<String> {
+        let (mut board, mg) = setup(fen);
+        san_to_move(&mut board, &mg, san).map(|m| m.as_notation(false))
+    }
+
+    #[test]
+    fn writes_moves() {
+        assert_eq!(san(FEN_START_POSITION, "e2e4"), "e4");
+        assert_eq!(san(FEN_START_POSITION, "g1f3"), "Nf3");
+        assert_eq!(san(FEN_KIWIPETE_POSITION, "e5f7"), "Nxf7");
+        assert_eq!(san(FEN_KIWIPETE_POSITION, "d5e6"), "dxe6");
+        assert_eq!(san(FEN_KIWIPETE_POSITION, "e1g1"), "O-O");
+        assert_eq!(san(FEN_KIWIPETE_POSITION, "e1c1"), "O-O-O");
+    }
+
+    #[test]
+    fn writes_disambiguation() {
+        // Queens on a1, c1 and a3 can all move to b2.
+        let fen = "8/8/8/8/6k1/Q7/8/Q1Q4K w - - 0 1";
+        assert_eq!(san(fen, "c1b2"), "Qcb2");
+        assert_eq!(san(fen, "a3b2"), "Q3b2");
+        assert_eq!(san(fen, "a1b2"), "Qa1b2");
+
+        // Only one of the knights can move to d2 without the other.
+        let fen = "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1";
+        assert_eq!(san(fen, "b1d2"), "Nbd2");
+        assert_eq!(san(fen, "f3d2"), "Nfd2");
+        assert_eq!(san(fen, "b1c3"), "Nc3");
+    }
+
+    #[test]
+    fn writes_check_and_mate() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        assert_eq!(san(fen, "h5f7"), "Qxf7#");
+        assert_eq!(san(fen, "c4f7"), "Bxf7+");
+
+        let fen = "8/4P3/8/8/k7/8/8/4K3 w - - 0 1";
+        assert_eq!(san(fen, "e7e8q"), "e8=Q+");
+        assert_eq!(san(fen, "e7e8n"), "e8=N");
+    }
+
+    #[test]
+    fn reads_moves() {
+        let fen = "8/8/8/8/6k1/Q7/8/Q1Q4K w - - 0 1";
+        assert_eq!(parse(fen, "Qcb2").as_deref(), Some("c1b2"));
+        assert_eq!(parse(fen, "Q3b2").as_deref(), Some("a3b2"));
+        assert_eq!(parse(fen, "Qa1b2").as_deref(), Some("a1b2"));
+        assert_eq!(parse(fen, "Qb2"), None);
+        assert_eq!(parse(fen, "Qab2"), None);
+        assert_eq!(parse(fen, "Q1b2"), None);
+
+        assert_eq!(parse(FEN_KIWIPETE_POSITION, "O-O").as_deref(), Some("e1g1"));
+        assert_eq!(
+            parse(FEN_KIWIPETE_POSITION, "O-O-O").as_deref(),
+            Some("e1c1")
+        );
+        assert_eq!(parse(FEN_START_POSITION, "O-O"), None);
+        assert_eq!(parse(FEN_START_POSITION, "e5"), None);
+        assert_eq!(parse(FEN_START_POSITION, "Nf4"), None);
+    }
+
+    #[test]
+    fn reads_sloppy_moves() {
+        let fen = FEN_START_POSITION;
+        assert_eq!(parse(fen, "e2e4").as_deref(), Some("e2e4"));
+        assert_eq!(parse(fen, "e2-e4").as_deref(), Some("e2e4"));
+        assert_eq!(parse(fen, "Pe4").as_deref(), Some("e2e4"));
+        assert_eq!(parse(fen, "g1f3").as_deref(), Some("g1f3"));
+        assert_eq!(parse(fen, "Ng1f3").as_deref(), Some("g1f3"));
+        assert_eq!(parse(fen, "Nf3!?").as_deref(), Some("g1f3"));
+        assert_eq!(parse(fen, "xyz"), None);
+        assert_eq!(parse(fen, ""), None);
+
+        let fen = "8/4P3/8/8/k7/8/8/4K3 w - - 0 1";
+        assert_eq!(parse(fen, "e8=Q+").as_deref(), Some("e7e8q"));
+        assert_eq!(parse(fen, "e8Q").as_deref(), Some("e7e8q"));
+        assert_eq!(parse(fen, "e7e8n").as_deref(), Some("e7e8n"));
+        assert_eq!(parse(fen, "e8"), None);
+
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(parse(fen, "0-0").as_deref(), Some("e1g1"));
+        assert_eq!(parse(fen, "o-o-o").as_deref(), Some("e1c1"));
+    }
+
+    #[test]
+    fn round_trips_all_moves() {
+        let (mut board, mg) = setup(FEN_KIWIPETE_POSITION);
+
+        for m in mg.legal_moves(&mut board) {
+            let san = move_to_san(&mut board, &mg, m);
+            let found = san_to_move(&mut board, &mg, &san).map(|m| m.as_notation(false));
+            assert_eq!(found, Some(m.as_notation(false)), "{san}");
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use comm::transport::{self, Transport};
 use engine::Engine;
+use extra::cmdline::Cmdline;
 mod board;
 mod comm;
 mod defs;
@@ -10,7 +12,22 @@ mod search;
 mod puzzle;
 
 fn main() {
-    // Start thread for the chess engine
-    let mut engine = Engine::new();
-    let _ = engine.run();
+    // Serve engine sessions over TCP if a port is given. Otherwise, run
+    // one engine that talks through the console.
+    match Cmdline::new().listen() {
+        Some(port) => {
+            if let Err(e) = transport::serve(port, session) {
+                println!("Can't listen on port {port}: {e}");
+            }
+        }
+        None => session(Transport::console()),
+    }
+}
+
+// Start thread for the chess engine
+fn session(transport: Transport) {
+    let mut engine = Engine::new(transport.clone());
+    if let Err(e) = engine.run() {
+        transport.write_line(&format!("Error: {e}"));
+    }
 }
@@ -6,6 +6,7 @@ mod movelist;
 
 use std::{
     collections::HashMap,
+    fmt,
     fs::File,
     sync::Arc,
     time::{Duration, Instant},
@@ -15,18 +16,16 @@ use defs::MoveData;
 
 use crate::{
     board::{
-        defs::{Pieces, Ranks, Squares, BB_RANKS, BB_SQUARES},
+        defs::{Pieces, Wings, BB_RANKS, BB_SQUARES},
         Board,
     },
     defs::{Bitboard, Castling, NrOf, Piece, Side, Sides, Square, EMPTY},
-    extra::{
-        bits,
-        print::{self, print_position},
-    },
+    extra::bits,
     search::defs::PerftSummary,
 };
 
 use crate::movegen::magics::Magic;
+use serde::Serialize;
 
 use self::defs::{Move, MoveList, MoveType, Shift};
 
@@ -45,7 +44,7 @@ pub struct MoveGenerator {
     bishop_magics: [Magic; NrOf::SQUARES],
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct MoveStats {
     captures: u64,
     en_passants: u64,
@@ -70,15 +69,18 @@ impl MoveStats {
             checkmates: 0,
         }
     }
-    pub fn log(&self) {
-        println!("Captures: {}", self.captures);
-        println!("En Passants: {}", self.en_passants);
-        println!("Castles: {}", self.castles);
-        println!("Promotions: {}", self.promotions);
-        println!("Checks: {}", self.checks);
-        println!("Discovery Checks: {}", self.discovery_checks);
-        println!("Double Checks: {}", self.double_checks);
-        println!("Checkmates: {}\n", self.checkmates);
+}
+
+impl fmt::Display for MoveStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Captures: {}", self.captures)?;
+        writeln!(f, "En Passants: {}", self.en_passants)?;
+        writeln!(f, "Castles: {}", self.castles)?;
+        writeln!(f, "Promotions: {}", self.promotions)?;
+        writeln!(f, "Checks: {}", self.checks)?;
+        writeln!(f, "Discovery Checks: {}", self.discovery_checks)?;
+        writeln!(f, "Double Checks: {}", self.double_checks)?;
+        writeln!(f, "Checkmates: {}", self.checkmates)
     }
 }
 
@@ -124,6 +126,24 @@ impl MoveGenerator {
         }
     }
 
+    // All legal moves for the side to move. Each move is tried on the
+    // board, so this is meant for move input and output, not the search.
+    pub fn legal_moves(&self, board: &mut Board) -> Vec<Move> {
+        let mut move_list = MoveList::new();
+        let mut stats = MoveStats::new();
+        self.generate_moves(board, &mut move_list, MoveType::All, &mut stats);
+
+        let mut legal_moves = Vec::new();
+        for i in 0..move_list.len() {
+            let m = move_list.get_move(i);
+            if board.make_move(m, self) {
+                board.unmake();
+                legal_moves.push(m);
+            }
+        }
+        legal_moves
+    }
+
     pub fn get_non_slider_moves(&self, piece: Piece, square: Square) -> Bitboard {
         match piece {
             Pieces::KING => self.king[square],
@@ -237,78 +257,41 @@ impl MoveGenerator {
         }
     }
 
+    // Generate castling moves. In Chess960 the king and rook can start on
+    // different files, but end up on the same squares as in standard chess.
+    // Castling is encoded as the king taking its own rook.
     pub fn castling(&self, board: &Board, move_list: &mut MoveList, stats: &mut MoveStats) -> bool {
         let player = board.side_to_move();
         let opponent = board.side_to_not_move();
-
-        let castle_perm_w = (board.gamestate.castling & (Castling::WK | Castling::WQ)) > 0;
-        let castle_perm_b = (board.gamestate.castling & (Castling::BK | Castling::BQ)) > 0;
-
         let bb_occupancy = board.occupancy();
+        let bb_rooks = board.get_pieces(Pieces::ROOK, player);
+        let king_from = board.king_square(player);
 
-        let mut bb_king = board.get_pieces(Pieces::KING, player);
-        let from = bits::next(&mut bb_king);
-        // White castling
-        if castle_perm_w && player == Sides::WHITE {
-            // kingside
-            if board.gamestate.castling & Castling::WK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F1] | BB_SQUARES[Squares::G1];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::F1)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, move_list, stats);
-                }
+        for wing in [Wings::KING, Wings::QUEEN] {
+            if board.gamestate.castling & Castling::RIGHTS[player][wing] == 0 {
+                continue;
             }
 
-            if board.gamestate.castling & Castling::WQ > 0 {
-                // Queenside
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B1] | BB_SQUARES[Squares::C1] | BB_SQUARES[Squares::D1];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::D1)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, move_list, stats);
-                }
+            let rook_from = board.castling_rooks[player][wing];
+            let (king_to, rook_to) = Board::castling_targets(player, wing);
+
+            // Every square the king and the rook pass, or land on, must be
+            // empty. The king and the castling rook themselves don't count.
+            let bb_castlers = BB_SQUARES[king_from] | BB_SQUARES[rook_from];
+            let bb_path = squares_between(king_from, king_to) | squares_between(rook_from, rook_to);
+            let is_blocked = (bb_occupancy & bb_path & !bb_castlers) > 0;
+
+            // The king may not castle out of, through or into check.
+            let mut bb_king_path = squares_between(king_from, king_to);
+            let mut is_attacked = false;
+            while bb_king_path > 0 && !is_attacked {
+                let square = bits::next(&mut bb_king_path);
+                is_attacked = self.square_attacked(board, opponent, square);
             }
-        }
 
-        // Black castling
-        if player == Sides::BLACK && castle_perm_b {
-            // Kingside
-            if board.gamestate.castling & Castling::BK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F8] | BB_SQUARES[Squares::G8];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::F8)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, move_list, stats);
-                }
-            }
-
-            // Queenside
-            if board.gamestate.castling & Castling::BQ > 0 {
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B8] | BB_SQUARES[Squares::C8] | BB_SQUARES[Squares::D8];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::D8)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, move_list, stats);
-                }
+            if (bb_rooks & BB_SQUARES[rook_from]) > 0 && !is_blocked && !is_attacked {
+                let to = BB_SQUARES[rook_from];
+                self.add_move(board, Pieces::KING, king_from, to, move_list, stats);
             }
         }
 
@@ -326,13 +309,19 @@ impl MoveGenerator {
     ) {
         let mut bb_to = to;
         let player = board.side_to_move();
-        let opponent = board.side_to_move();
+        let bb_own = board.bb_side[player];
         let promotion_rank = Board::promotion_rank(player);
         let is_pawn = piece == Pieces::PAWN;
 
         while bb_to > 0 {
             let to_square = bits::next(&mut bb_to);
-            let capture = board.piece_list[to_square];
+            // A king "taking" its own rook is castling.
+            let castling = (piece == Pieces::KING) && (bb_own & BB_SQUARES[to_square]) > 0;
+            let capture = if castling {
+                Pieces::NONE
+            } else {
+                board.piece_list[to_square]
+            };
             let en_passant = match board.gamestate.en_passant {
                 Some(square) => is_pawn && (square as usize == to_square),
                 None => false,
@@ -340,7 +329,6 @@ impl MoveGenerator {
 
             let promotion = is_pawn && Board::square_on_rank(to_square, promotion_rank);
             let double_push = is_pawn && ((to_square as i8 - from as i8).abs() == 16);
-            let castling = (piece == Pieces::KING) && ((to_square as i8 - from as i8).abs() == 2);
 
             // add all data into a 64 bit variable
             let mut move_data = (piece)
@@ -453,7 +441,7 @@ impl MoveGenerator {
                 if mov.data > 0 {
                     if (board.make_move(*mov, &movegen)) {
                         all_move_data = false;
-                        let the_move = format!("{}", mov.as_string());
+                        let the_move = mov.as_notation(board.chess960);
                         let nodes = Self::perft_results(
                             depth - 1,
                             &mut board,
@@ -553,3 +541,67 @@ impl MoveGenerator {
         end_time - start_time
     }
 }
+
+// All squares from one square to another on the same rank, both included.
+fn squares_between(a: Square, b: Square) -> Bitboard {
+    let (low, high) = (a.min(b), a.max(b));
+    (BB_SQUARES[high] << 1).wrapping_sub(BB_SQUARES[low])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::MoveGenerator;
+    use crate::{
+        board::Board,
+        defs::{FEN_KIWIPETE_POSITION, FEN_START_POSITION},
+    };
+
+    // Count the leaf nodes of the position at each depth, starting at 1.
+    fn assert_perft(fen: &str, expected: &[i32]) {
+        let mg = Arc::new(MoveGenerator::new());
+        let mut board = Board::new();
+        board.read_fen(Some(fen)).expect("valid FEN");
+
+        for (i, nodes) in expected.iter().enumerate() {
+            let depth = i as i8 + 1;
+            let summary = MoveGenerator::go_perft_results(board.clone(), depth, &mg);
+            assert_eq!(summary.nodes, *nodes, "{fen} at depth {depth}");
+        }
+    }
+
+    #[test]
+    fn perft_standard() {
+        assert_perft(FEN_START_POSITION, &[20, 400, 8_902, 197_281]);
+        assert_perft(FEN_KIWIPETE_POSITION, &[48, 2_039, 97_862]);
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2_812, 43_238],
+        );
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9_467],
+        );
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1_486, 62_379],
+        );
+    }
+
+    #[test]
+    fn perft_chess960() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12_189, 326_672],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18_002, 667_366],
+        );
+        assert_perft(
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            &[20, 479, 10_471, 273_318],
+        );
+    }
+}
@@ -8,8 +8,11 @@ pub const NOT_HG_FILE: u64 = 4557430888798830399;
 
 pub use super::{magics::Magic, movelist::MoveList};
 use crate::{
-    board::defs::{PIECE_CHAR_CAPS, PIECE_CHAR_SMALL, PIECE_NAME, SQUARE_NAME},
-    defs::{Castling, Piece, Square},
+    board::{
+        defs::{Wings, PIECE_CHAR_CAPS, PIECE_CHAR_SMALL, PIECE_NAME, SQUARE_NAME},
+        Board,
+    },
+    defs::{Castling, Piece, Sides, Square},
 };
 
 const MOVE_ONLY: usize = 0x00_00_00_00_00_FF_FF_FF;
@@ -140,17 +143,46 @@ impl Move {
         (self.data & MOVE_ONLY) as u32
     }
 
-    pub fn as_string(&self) -> String {
+    // Castling is stored as the king taking its own rook. The rook is on
+    // the king side if it is on a higher file than the king.
+    pub fn castling_wing(&self) -> usize {
+        if self.to() > self.from() {
+            Wings::KING
+        } else {
+            Wings::QUEEN
+        }
+    }
+
+    // The to-square as written in move notation. Standard chess writes
+    // castling as the king's move, Chess960 as the king taking the rook.
+    pub fn notation_to(&self, chess960: bool) -> Square {
+        if self.castling() && !chess960 {
+            let side = if self.from() < 8 {
+                Sides::WHITE
+            } else {
+                Sides::BLACK
+            };
+            Board::castling_targets(side, self.castling_wing()).0
+        } else {
+            self.to()
+        }
+    }
+
+    pub fn as_notation(&self, chess960: bool) -> String {
         format!(
             "{}{}{}",
             SQUARE_NAME[self.from()],
-            SQUARE_NAME[self.to()],
+            SQUARE_NAME[self.notation_to(chess960)],
             PIECE_CHAR_SMALL[self.promoted()]
         )
     }
+
+    pub fn as_string(&self) -> String {
+        self.as_notation(false)
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct ShortMove {
     data: u32,
 }
@@ -1,5 +1,4 @@
 use std::{
-    env,
     sync::{Arc, Mutex},
     thread::{self, JoinHandle},
 };
@@ -16,15 +15,19 @@ use crate::{
     movegen::MoveGenerator,
 };
 
-use self::defs::{
-    SearchControl, SearchInfo, SearchParams, SearchRefs, SearchReport, SearchTerminate,
+use self::{
+    defs::{SearchControl, SearchInfo, SearchParams, SearchRefs, SearchReport, SearchTerminate},
+    helpers::MoveBook,
 };
 
 mod alpha_beta;
+mod bench;
 mod bestmove;
 pub mod defs;
-mod helpers;
+pub mod helpers;
+pub mod heuristics;
 pub mod search_routine;
+pub mod skill;
 mod sorting;
 pub mod time;
 pub mod utils;
@@ -48,7 +51,7 @@ impl Search {
         board: Arc<Mutex<Board>>,       // Arc pointer to engine's board.
         mg: Arc<MoveGenerator>,         // Arc pointer to engine's move generator.
         tt: Arc<Mutex<TT<SearchData>>>,
-        tt_enabled: bool,
+        book: Arc<Mutex<Arc<MoveBook>>>, // Arc pointer to engine's opening book.
     ) {
         // Set up a channel for incoming commands
         let (control_tx, control_rx) = crossbeam_channel::unbounded::<SearchControl>();
@@ -62,6 +65,7 @@ impl Search {
             let arc_board = Arc::clone(&board);
             let arc_mg = Arc::clone(&mg);
             let arc_tt = Arc::clone(&tt);
+            let arc_book = Arc::clone(&book);
             // let arc_tt = Arc::clone(&tt);
             let mut search_params = SearchParams::new();
             let mut search_type = SearchType::Nothing;
@@ -82,24 +86,37 @@ impl Search {
                     }
                     SearchControl::Stop => halt = true,
                     SearchControl::Quit => quit = true,
-                    SearchControl::Nothing => (),
+                    SearchControl::PonderHit | SearchControl::Nothing => (),
                 }
 
                 // Search isn't halted and not going to quit.
                 if !halt && !quit {
                     match search_type {
                         SearchType::Search => {
-                            Search::search_best_move(
-                                halt,
-                                quit,
+                            let terminate = Search::search_best_move(
                                 &arc_board,
                                 &arc_mg,
                                 &arc_tt,
-                                tt_enabled,
+                                &arc_book,
                                 &control_rx,
                                 &t_report_tx,
-                                search_params,
+                                search_params.clone(),
                             );
+
+                            // If the search was finished due to a Quit
+                            // command, then quit the search thread as well.
+                            quit = terminate == SearchTerminate::Quit;
+                        }
+                        SearchType::Bench => {
+                            let terminate = Search::bench(
+                                &arc_mg,
+                                &arc_tt,
+                                search_params.tt_enabled,
+                                &control_rx,
+                                &t_report_tx,
+                                search_params.depth,
+                            );
+                            quit = terminate == SearchTerminate::Quit;
                         }
                         SearchType::Perft => {
                             Search::perft_score(
@@ -111,6 +128,9 @@ impl Search {
                         }
                         _ => (),
                     }
+
+                    // Wait for the next Start command.
+                    halt = true;
                 }
             }
         });
@@ -153,16 +173,14 @@ impl Search {
     }
 
     pub fn search_best_move(
-        mut halt: bool,
-        mut quit: bool,
         board: &Arc<Mutex<Board>>,
         arc_mg: &Arc<MoveGenerator>,
         arc_tt: &Arc<Mutex<TT<SearchData>>>,
-        tt_enabled: bool,
+        arc_book: &Arc<Mutex<Arc<MoveBook>>>,
         control_rx: &Receiver<SearchControl>,
         t_report_tx: &Sender<Information>,
         mut search_params: SearchParams,
-    ) {
+    ) -> SearchTerminate {
         // Copy the current board to be used in this thread.
         let mtx_board = board.lock().expect("lock failed");
         let mut board = mtx_board.clone();
@@ -171,39 +189,30 @@ impl Search {
         // Create a place to put search information
         let mut search_info = SearchInfo::new();
 
+        // Take the book as it is now. The engine may load another one while
+        // searching; this search keeps using the one it started with.
+        let book = Arc::clone(&arc_book.lock().expect("lock failed"));
+
         // Create references to all needed information and structures.
-        let path = env::current_dir().unwrap();
-        let formatted_path = format!("{}/../book.txt", path.display());
         let mut search_refs = SearchRefs {
             board: &mut board,
             move_generator: &arc_mg,
             tt: &arc_tt,
-            tt_enabled,
+            tt_enabled: search_params.tt_enabled,
             search_info: &mut search_info,
             search_params: &mut search_params,
             control_rx: &control_rx,
             report_tx: &t_report_tx,
-            book: &Search::load_book(&formatted_path),
+            book: &book,
         };
 
         // Start the search using Iterative Deepening.
-        let (best_move, terminate) = Search::search_routine(&mut search_refs);
+        let (best_move, ponder_move, terminate) = Search::search_routine(&mut search_refs);
 
         // Inform the engine that the search has finished.
-        let information = Information::Search(SearchReport::Finished(best_move));
+        let information = Information::Search(SearchReport::Finished(best_move, ponder_move));
         t_report_tx.send(information).expect("channel failed");
 
-        // If the search was finished due to a Stop or Quit
-        // command then either halt or quit the search.
-        match terminate {
-            SearchTerminate::Stop => {
-                halt = true;
-            }
-            SearchTerminate::Quit => {
-                halt = true;
-                quit = true;
-            }
-            SearchTerminate::Nothing => (),
-        }
+        terminate
     }
 }
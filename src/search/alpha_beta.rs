@@ -4,16 +4,21 @@ use rand::thread_rng;
 use crate::{
     board::{defs::Pieces, Board},
     engine::transposition::{HashFlag, SearchData},
+    evaluation::evaluate_position,
     extra::parse::algebraic_move_to_number,
     movegen::{
         defs::{Move, MoveList, MoveType, ShortMove},
         MoveStats,
     },
-    search::defs::SearchTerminate,
+    search::defs::{SearchMode, SearchTerminate},
 };
 
 use super::{
-    defs::{SearchRefs, CHECKMATE, CHECK_TERMINATION, DRAW, INF, STALEMATE},
+    defs::{
+        SearchRefs, CHECKMATE, CHECKMATE_THRESHOLD, CHECK_TERMINATION, DRAW, INF, LMP_BASE_MOVES,
+        LMP_MAX_DEPTH, LMR_BASE, LMR_DIVISOR, LMR_MIN_DEPTH, LMR_MIN_MOVES,
+        NULL_MOVE_ADAPTIVE_DEPTH, NULL_MOVE_MIN_DEPTH, NULL_MOVE_VERIFY_DEPTH, STALEMATE,
+    },
     Search,
 };
 
@@ -26,11 +31,14 @@ impl Search {
         refs: &mut SearchRefs,
     ) -> i16 {
         let is_root = refs.search_info.ply == 0; // At root if no moves were played.
+        let is_pv = beta > alpha + 1; // PV nodes are searched with an open window.
+        let is_mate_search = refs.search_params.has_mode(SearchMode::Mate); // Don't prune.
         let mut pvs = false; // Used for PVS (Principal Variation Search)
 
         // Check if termination condition is met
         if refs.search_info.nodes & CHECK_TERMINATION == 0 {
             Search::check_termination(refs);
+            Search::send_stats(refs);
         }
 
         // If time is up, abort. This depth won't be considered in
@@ -89,8 +97,49 @@ impl Search {
             }
         }
 
+        // Null move pruning: let the opponent move twice in a row. If a
+        // reduced search still fails high, a real move would almost always
+        // do so as well, and this node can be cut off. Only done in the
+        // zero window nodes of PVS.
+        if Search::null_move_allowed(depth, alpha, beta, is_root, is_check, refs) {
+            let reduction = if depth > NULL_MOVE_ADAPTIVE_DEPTH {
+                3
+            } else {
+                2
+            };
+            let mut null_pv = Vec::new();
+
+            refs.board.make_null_move();
+            refs.search_info.ply += 1;
+            let null_depth = (depth - 1 - reduction).max(0);
+            let score = -Search::alpha_beta(null_depth, -beta, -beta + 1, &mut null_pv, refs);
+            refs.board.unmake_null_move();
+            refs.search_info.ply -= 1;
+
+            if refs.search_info.interupted() {
+                return 0;
+            }
+
+            if score >= beta {
+                // Deep in the tree, a zugzwang would cost the most. Verify
+                // the cutoff with a reduced search without null moves.
+                let verified = depth < NULL_MOVE_VERIFY_DEPTH || {
+                    refs.search_info.verifying_null_move = true;
+                    let v =
+                        Search::alpha_beta(depth - reduction, beta - 1, beta, &mut null_pv, refs);
+                    refs.search_info.verifying_null_move = false;
+                    v >= beta
+                };
+
+                if verified {
+                    return beta;
+                }
+            }
+        }
+
         // Generate and score moves
         let mut legal_moves = 0;
+        let mut excluded_moves = 0; // Legal root moves skipped in this search
         let mut move_list = MoveList::new();
         let mut move_stats = MoveStats::new();
         refs.move_generator.generate_moves(
@@ -100,12 +149,15 @@ impl Search {
             &mut move_stats,
         );
 
-        // Check the book for the current position
+        // Check the book for the current position. In MultiPV mode the
+        // engine is analyzing, so the book is not used.
         let fen = Board::normalize_fen(&refs.board.create_fen()).to_string();
-        if let Some(book_moves) = refs.book.get(&fen) {
+        let use_book = refs.search_params.multi_pv <= 1;
+        if let Some(book_moves) = refs.book.get(&fen).filter(|_| use_book) {
             if !book_moves.is_empty()
-                && Self::handle_book_moves(&book_moves, is_root, &move_list, possible_moves)
+                && Self::handle_book_moves(&book_moves, is_root, &move_list, possible_moves, refs)
             {
+                refs.search_info.root_book_move |= is_root;
                 return 0;
             }
         }
@@ -121,10 +173,26 @@ impl Search {
         // Holds the best move in the loop
         let mut best_possible_move = ShortMove::new(0);
 
+        // Quiet moves searched without a cutoff, to lower their history.
+        let mut quiets_tried = MoveList::new();
+
         for x in 0..move_list.len() {
             Search::swap_move(&mut move_list, x);
 
             let current_move = move_list.get_move(x);
+
+            // In MultiPV mode, skip root moves that are already in a line,
+            // and skip root moves that are not in "searchmoves". The legal
+            // ones are counted apart: the position isn't mate or stalemate,
+            // but they shouldn't count as searched moves either.
+            if is_root && Search::is_excluded(current_move, refs) {
+                if refs.board.make_move(current_move, refs.move_generator) {
+                    refs.board.unmake();
+                    excluded_moves += 1;
+                }
+                continue;
+            }
+
             if !refs.board.make_move(current_move, refs.move_generator) {
                 continue;
             }
@@ -136,6 +204,49 @@ impl Search {
             }
 
             legal_moves += 1;
+
+            // A quiet move is one that doesn't change the material, isn't
+            // a killer, and doesn't get out of or give check. A mate search
+            // must see every move, so it doesn't treat any move as quiet.
+            let gives_check = refs.move_generator.square_attacked(
+                refs.board,
+                refs.board.side_to_not_move(),
+                refs.board.king_square(refs.board.side_to_move()),
+            );
+            let is_quiet = !is_mate_search
+                && !is_check
+                && !gives_check
+                && current_move.captured() == Pieces::NONE
+                && current_move.promoted() == Pieces::NONE
+                && !Search::is_killer(current_move, refs);
+
+            // Late move pruning: near the leaves, once enough moves have
+            // been searched, the quiet moves that are left are skipped.
+            // They are sorted last and are unlikely to raise alpha.
+            if is_quiet
+                && !is_pv
+                && depth <= LMP_MAX_DEPTH
+                && legal_moves > LMP_BASE_MOVES + (depth * depth) as u8
+                && best_eval_score > -CHECKMATE_THRESHOLD
+            {
+                refs.board.unmake();
+                continue;
+            }
+
+            // Let the GUI know which root move is being searched.
+            if is_root {
+                Search::send_current_move(current_move, legal_moves, refs);
+            }
+
+            // Late move reduction: search quiet moves late in the list less
+            // deep. They are searched at full depth only if they turn out
+            // to be better than expected.
+            let reduction = if is_quiet && depth >= LMR_MIN_DEPTH && legal_moves > LMR_MIN_MOVES {
+                Search::late_move_reduction(depth, legal_moves, is_pv)
+            } else {
+                0
+            };
+
             refs.search_info.ply += 1;
 
             let mut node_pv = Vec::new();
@@ -145,12 +256,24 @@ impl Search {
                 refs.search_info.seldepth = refs.search_info.ply;
             }
 
-            // Perform alpha-beta search
+            // Perform alpha-beta search. A draw is scored as such, and the
+            // move is taken back like any other.
             if !Search::is_draw(refs) {
                 // Try pvs if possible
-                if pvs {
-                    eval_score =
-                        -Search::alpha_beta(depth - 1, -alpha - 1, -alpha, &mut node_pv, refs);
+                if pvs || reduction > 0 {
+                    eval_score = -Search::alpha_beta(
+                        depth - 1 - reduction,
+                        -alpha - 1,
+                        -alpha,
+                        &mut node_pv,
+                        refs,
+                    );
+
+                    // The reduced search failed high: search at full depth.
+                    if reduction > 0 && eval_score > alpha {
+                        eval_score =
+                            -Search::alpha_beta(depth - 1, -alpha - 1, -alpha, &mut node_pv, refs);
+                    }
 
                     // Failed pvs?
                     if eval_score > alpha && eval_score < beta {
@@ -160,8 +283,6 @@ impl Search {
                 } else {
                     eval_score = -Search::alpha_beta(depth - 1, -beta, -alpha, &mut node_pv, refs);
                 }
-            } else {
-                return -INF;
             }
 
             refs.board.unmake();
@@ -176,28 +297,45 @@ impl Search {
             // Beta cutoff: this move is so good for our opponent, that we
             // do not search any further. Insert into TT and return beta.
             if eval_score >= beta {
-                refs.tt.lock().expect("Error locking TT").insert(
-                    refs.board.gamestate.zobrist_key,
-                    SearchData::create(
-                        depth,
-                        refs.search_info.ply,
-                        HashFlag::Beta,
-                        beta,
-                        best_possible_move,
-                    ),
-                );
+                // As below: a root search with excluded moves isn't the
+                // result for the position itself.
+                if !(is_root && Search::skips_root_moves(refs)) {
+                    refs.tt.lock().expect("Error locking TT").insert(
+                        refs.board.gamestate.zobrist_key,
+                        SearchData::create(
+                            depth,
+                            refs.search_info.ply,
+                            HashFlag::Beta,
+                            beta,
+                            best_possible_move,
+                        ),
+                    );
+                }
+
+                // At the root, this only happens when the aspiration window
+                // fails high. Keep the move as the line, so the search can
+                // report it.
+                if is_root {
+                    possible_moves.clear();
+                    possible_moves.push(current_move);
+                    possible_moves.append(&mut node_pv);
+                }
 
                 // If the move is not a capture but still causes a
                 // beta-cutoff, then store it as a killer move and update
                 // the history heuristics.
                 if current_move.captured() == Pieces::NONE {
                     Search::store_killer_move(current_move, refs);
-                    //Search::update_history_heuristic(current_move, depth, refs);
+                    Search::update_history_heuristic(current_move, &quiets_tried, depth, refs);
                 }
 
                 return beta;
             }
 
+            if current_move.captured() == Pieces::NONE {
+                quiets_tried.push(current_move);
+            }
+
             if eval_score > alpha {
                 // Save our better eval in alpha
                 alpha = eval_score;
@@ -216,7 +354,7 @@ impl Search {
         }
 
         // Check for checkmate or stalemate
-        if legal_moves == 0 {
+        if legal_moves == 0 && excluded_moves == 0 {
             return if is_check {
                 // The return value is minus CHECKMATE, because if we have
                 // no legal moves and are in check, it's game over.
@@ -226,6 +364,12 @@ impl Search {
             };
         }
 
+        // Don't store the result of a root search with excluded moves. It
+        // isn't the result for the position itself.
+        if is_root && Search::skips_root_moves(refs) {
+            return alpha;
+        }
+
         // We save the best move we found for us; with an ALPHA flag if we
         // didn't improve alpha, or EXACT if we did raise alpha.
         refs.tt.lock().expect("Failed locking tt table").insert(
@@ -242,34 +386,118 @@ impl Search {
         alpha
     }
 
+    // A null move is not tried at the root, in check, right after another
+    // null move or while verifying one, or when the side to move only has
+    // pawns left: that is where zugzwang lurks. Nor is it tried when the
+    // position is already below beta, or beta is a mate score. A mate
+    // search must see every move, so there it is not tried at all.
+    fn null_move_allowed(
+        depth: i8,
+        alpha: i16,
+        beta: i16,
+        is_root: bool,
+        is_check: bool,
+        refs: &SearchRefs,
+    ) -> bool {
+        let board = &refs.board;
+        let us = board.side_to_move();
+        let king_and_pawns = board.bb_pieces[us][Pieces::KING] | board.bb_pieces[us][Pieces::PAWN];
+        let has_pieces = board.bb_side[us] & !king_and_pawns > 0;
+        let after_null = board
+            .history
+            .len()
+            .checked_sub(1)
+            .is_some_and(|last| board.history.get_ref(last).next_move.get_move() == 0);
+
+        !is_root
+            && !refs.search_params.has_mode(SearchMode::Mate)
+            && !is_check
+            && !after_null
+            && !refs.search_info.verifying_null_move
+            && has_pieces
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && beta == alpha + 1
+            && beta.abs() < CHECKMATE_THRESHOLD
+            && evaluate_position(board) >= beta
+    }
+
+    // Reduction grows with the logarithm of both the depth and the number
+    // of moves searched before. Moves in PV nodes are reduced one ply less.
+    // At least one ply is left to search.
+    fn late_move_reduction(depth: i8, move_number: u8, is_pv: bool) -> i8 {
+        let ln = |x: f32| x.ln();
+        let r = LMR_BASE + ln(depth as f32) * ln(move_number as f32) / LMR_DIVISOR;
+        let r = r as i8 - is_pv as i8;
+        r.clamp(0, depth - 2)
+    }
+
+    fn is_killer(m: Move, refs: &SearchRefs) -> bool {
+        let ply = refs.search_info.ply as usize;
+        refs.search_info.killer_moves[ply]
+            .iter()
+            .any(|k| k.get_move() == m.get_move())
+    }
+
+    fn is_excluded(m: Move, refs: &SearchRefs) -> bool {
+        let in_line = refs
+            .search_info
+            .excluded_moves
+            .iter()
+            .any(|e| e.get_move() == m.get_move());
+
+        in_line || !Search::is_search_move(m, refs)
+    }
+
+    // True if the root search leaves out some of the root moves, because
+    // of MultiPV or "searchmoves".
+    fn skips_root_moves(refs: &SearchRefs) -> bool {
+        !refs.search_info.excluded_moves.is_empty() || !refs.search_params.search_moves.is_empty()
+    }
+
+    // True if the move is allowed by "go searchmoves" (or no such
+    // restriction was given).
+    fn is_search_move(m: Move, refs: &SearchRefs) -> bool {
+        let search_moves = &refs.search_params.search_moves;
+        search_moves.is_empty() || search_moves.iter().any(|s| s.get_move() == m.get_move())
+    }
+
     fn handle_book_moves(
         book_moves: &Vec<(String, u32)>,
         is_root: bool,
         move_list: &MoveList,
         possible_moves: &mut Vec<Move>,
+        refs: &SearchRefs,
     ) -> bool {
         possible_moves.clear();
 
+        // Find the move in the move list that belongs to a book move.
+        let find_move = |book_move: &(String, u32)| -> Option<Move> {
+            let parsed_move = algebraic_move_to_number(&book_move.0).ok()?;
+            (0..move_list.len())
+                .map(|i| move_list.get_move(i))
+                .find(|current| {
+                    parsed_move.0 == current.from()
+                        && parsed_move.1 == current.notation_to(false)
+                        && parsed_move.2 == current.promoted()
+                })
+        };
+
         let selected_move = if is_root {
+            // At the root, only pick book moves allowed by "searchmoves".
+            let candidates: Vec<Move> = book_moves
+                .iter()
+                .filter_map(find_move)
+                .filter(|m| Search::is_search_move(*m, refs))
+                .collect();
             let mut rng = thread_rng();
-            book_moves.choose(&mut rng)
+            candidates.choose(&mut rng).copied()
         } else {
-            book_moves.first()
+            book_moves.first().and_then(find_move)
         };
 
         if let Some(book_move) = selected_move {
-            if let Ok(parsed_move) = algebraic_move_to_number(&book_move.0) {
-                for i in 0..move_list.len() {
-                    let current = move_list.get_move(i);
-                    if parsed_move.0 == current.from()
-                        && parsed_move.1 == current.to()
-                        && parsed_move.2 == current.promoted()
-                    {
-                        possible_moves.push(current);
-                        return true;
-                    }
-                }
-            }
+            possible_moves.push(book_move);
+            return true;
         }
         false
     }
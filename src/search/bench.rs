@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use super::{
+    defs::{
+        BenchSummary, SearchControl, SearchInfo, SearchMode, SearchParams, SearchRefs,
+        SearchReport, SearchTerminate,
+    },
+    helpers::MoveBook,
+    Search,
+};
+use crate::{
+    board::Board,
+    defs::{FEN_KIWIPETE_POSITION, FEN_START_POSITION},
+    engine::{
+        defs::Information,
+        transposition::{SearchData, TT},
+    },
+    movegen::MoveGenerator,
+};
+
+// The positions searched by "bench": openings, middle games with tactics,
+// and a few endgames. Searching them to a fixed depth always takes the
+// same number of nodes, as long as the search itself doesn't change. This
+// shows how much a change to the search prunes, or costs.
+const BENCH_POSITIONS: [&str; 9] = [
+    FEN_START_POSITION,
+    FEN_KIWIPETE_POSITION,
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+];
+
+impl Search {
+    // Search each bench position to the given depth, starting with an
+    // empty TT, and report the nodes it took. The intermediate results of
+    // the searches are not sent to the engine.
+    pub fn bench(
+        arc_mg: &Arc<MoveGenerator>,
+        arc_tt: &Arc<Mutex<TT<SearchData>>>,
+        tt_enabled: bool,
+        control_rx: &Receiver<SearchControl>,
+        t_report_tx: &Sender<Information>,
+        depth: i8,
+    ) -> SearchTerminate {
+        let (quiet_tx, _quiet_rx) = crossbeam_channel::unbounded::<Information>();
+        let book = MoveBook::new();
+        let mut terminate = SearchTerminate::Nothing;
+        let mut summary = BenchSummary {
+            depth,
+            nodes: Vec::new(),
+            time: 0,
+            interrupted: false,
+        };
+
+        for fen in BENCH_POSITIONS {
+            let mut board = Board::new();
+            if board.read_fen(Some(fen)).is_err() {
+                continue;
+            }
+            arc_tt.lock().expect("Error locking TT").clear();
+
+            let mut search_params = SearchParams::new();
+            search_params.depth = depth;
+            search_params.quiet = true;
+            search_params.add_mode(SearchMode::Depth);
+
+            let mut search_info = SearchInfo::new();
+            let mut search_refs = SearchRefs {
+                board: &mut board,
+                move_generator: arc_mg,
+                tt: arc_tt,
+                tt_enabled,
+                search_info: &mut search_info,
+                search_params: &mut search_params,
+                control_rx,
+                report_tx: &quiet_tx,
+                book: &book,
+            };
+
+            (_, _, terminate) = Search::search_routine(&mut search_refs);
+
+            summary.time += search_info.time_elapsed();
+            if search_info.interupted() {
+                summary.interrupted = true;
+                break;
+            }
+            summary.nodes.push(search_info.nodes);
+        }
+
+        let information = Information::Search(SearchReport::BenchScore(summary));
+        t_report_tx.send(information).expect("channel failed");
+
+        terminate
+    }
+}
@@ -8,17 +8,19 @@ use crate::{
     board::Board,
     defs::MAX_PLY,
     engine::{
-        defs::Information,
+        defs::{EngineOptionDefaults, Information},
         transposition::{SearchData, TT},
     },
+    evaluation::wdl::Wdl,
     movegen::{
         defs::{Move, ShortMove},
         MoveGenerator, MoveStats,
     },
 };
 use crossbeam_channel::{Receiver, Sender};
+use serde::Serialize;
 
-use super::helpers::MoveBook;
+use super::{helpers::MoveBook, heuristics::Heuristics};
 // Some const for searching
 pub const INF: i16 = 25_000;
 pub const CHECKMATE: i16 = 24_000;
@@ -27,8 +29,26 @@ pub const STALEMATE: i16 = 0;
 pub const DRAW: i16 = 0;
 pub const CHECK_TERMINATION: usize = 0x7FF; // 2.047 nodes
 pub const MAX_KILLER_MOVES: usize = 2;
+pub const MIN_TIME_STATS: u128 = 2_000; // msecs between search stats updates
+pub const MIN_TIME_CURR_MOVE: u128 = 1_000; // msecs between current move updates
+pub const BENCH_DEPTH: i8 = 7; // Default depth to search the bench positions to
+pub const NULL_MOVE_MIN_DEPTH: i8 = 3; // Shallowest depth to try a null move
+pub const NULL_MOVE_ADAPTIVE_DEPTH: i8 = 6; // Above this depth, reduce by 3 instead of 2
+pub const NULL_MOVE_VERIFY_DEPTH: i8 = 8; // From this depth, verify null move cutoffs
+pub const LMR_MIN_DEPTH: i8 = 3; // Shallowest depth to reduce late moves
+pub const LMR_MIN_MOVES: u8 = 3; // Moves searched at full depth before reducing
+pub const LMR_BASE: f32 = 0.75; // Reduction = base + ln(depth) * ln(moves) / divisor
+pub const LMR_DIVISOR: f32 = 2.25;
+pub const LMP_MAX_DEPTH: i8 = 3; // Deepest depth to prune late quiet moves
+pub const LMP_BASE_MOVES: u8 = 3; // Moves kept: base + depth * depth
+pub const ASPIRATION_MIN_DEPTH: i8 = 4; // Shallowest depth to use an aspiration window
+pub const ASPIRATION_WINDOW: i16 = 1; // Initial distance of the window to the last score (pawns)
+pub const ASPIRATION_MAX_SCORE: i16 = 10; // Beyond this score (pawns), don't use a window
+pub const HISTORY_MAX: i32 = 16_384; // History scores stay within plus/minus this value
+pub const HISTORY_BONUS_MAX: i32 = 1_536; // Largest history change of a single cutoff
+pub const CONTINUATION_PLIES: usize = 2; // Earlier moves used by the continuation history
 
-pub type SearchResult = (Move, SearchTerminate);
+pub type SearchResult = (Move, Option<Move>, SearchTerminate);
 type KillerMoves = [[ShortMove; MAX_KILLER_MOVES]; MAX_PLY as usize];
 
 // Ways to terminate a search.
@@ -69,6 +89,7 @@ impl GameTime {
 pub enum SearchType {
     Search,
     Perft,
+    Bench,
     Nothing,
 }
 
@@ -76,34 +97,45 @@ pub enum SearchType {
 // These commands can be used by the engine thread to control the search.
 pub enum SearchControl {
     Start(SearchParams, SearchType),
+    PonderHit, // The opponent played the expected move.
     Stop,
     Quit,
     Nothing,
 }
 
 // SearchMode lists how the search termination criteria will be evaluated,
-// to see if the search has to be stopped.
+// to see if the search has to be stopped. A search can have several of
+// these limits at the same time; it stops as soon as one of them is hit.
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum SearchMode {
     Depth,    // Run until requested depth is reached.
     MoveTime, // Run until 'time per move' is used up.
     Nodes,    // Run until the number of requested nodes was reached.
     GameTime, // Search determines when to quit, depending on available time.
+    Mate,     // Run until a mate in the requested number of moves is found.
     Infinite, // Run forever, until the 'stop' command is received.
-    Nothing,  // No search mode has been defined.
 }
 
 // This struct holds all the search parameters as set by the engine thread.
 // (These parameters are either default, or provided by the user interface
 // before the game starts.)
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct SearchParams {
-    pub depth: i8,               // Maximum depth to search to
-    pub move_time: u128,         // Maximum time per move to search
-    pub nodes: usize,            // Maximum number of nodes to search
-    pub game_time: GameTime,     // Time available for entire game
-    pub search_mode: SearchMode, // Defines the mode to search in
-    pub quiet: bool,             // No intermediate search stats updates
+    pub depth: i8,                     // Maximum depth to search to
+    pub move_time: u128,               // Maximum time per move to search
+    pub nodes: usize,                  // Maximum number of nodes to search
+    pub game_time: GameTime,           // Time available for entire game
+    pub search_modes: Vec<SearchMode>, // Limits that are active in this search
+    pub quiet: bool,                   // No intermediate search stats updates
+    pub move_overhead: u128,           // Time reserved for GUI/network lag
+    pub ponder: bool,                  // Searching on the opponent's time
+    pub multi_pv: u8,                  // Number of best lines to search
+    pub skill_lines: u8,               // Lines to pick a weaker move from
+    pub search_moves: Vec<ShortMove>,  // Root moves to search (all if empty)
+    pub mate: u8,                      // Mate in X moves to search for
+    pub show_wdl: bool,                // Report win/draw/loss chances
+    pub skill_level: u8,               // Playing strength; see Skill
+    pub tt_enabled: bool,              // Use the TT (Hash is not 0)
 }
 
 impl SearchParams {
@@ -113,13 +145,49 @@ impl SearchParams {
             move_time: 0,
             nodes: 0,
             game_time: GameTime::new(0, 0, 0, 0, None),
-            search_mode: SearchMode::Nothing,
+            search_modes: Vec::new(),
             quiet: false,
+            move_overhead: EngineOptionDefaults::MOVE_OVERHEAD_DEFAULT,
+            ponder: false,
+            multi_pv: EngineOptionDefaults::MULTI_PV_DEFAULT,
+            skill_lines: 0,
+            search_moves: Vec::new(),
+            mate: 0,
+            show_wdl: EngineOptionDefaults::SHOW_WDL_DEFAULT,
+            skill_level: EngineOptionDefaults::SKILL_LEVEL_DEFAULT,
+            tt_enabled: EngineOptionDefaults::HASH_DEFAULT > 0,
+        }
+    }
+
+    // Activate a limit. Each limit is only kept once.
+    pub fn add_mode(&mut self, mode: SearchMode) {
+        if !self.has_mode(mode) {
+            self.search_modes.push(mode);
+        }
+    }
+
+    pub fn has_mode(&self, mode: SearchMode) -> bool {
+        self.search_modes.contains(&mode)
+    }
+
+    // Called when all the limits given by the GUI are collected.
+    pub fn finish_limits(&mut self, game_time: GameTime) {
+        // If a clock was sent, the engine has to manage its time.
+        let has_time = game_time.white_time > 0 || game_time.black_time > 0;
+        let has_inc = game_time.white_time_incr > 0 || game_time.black_time_incr > 0;
+        if has_time || has_inc {
+            self.game_time = game_time;
+            self.add_mode(SearchMode::GameTime);
+        }
+
+        // Without any limits, search until "stop" is received.
+        if self.search_modes.is_empty() {
+            self.add_mode(SearchMode::Infinite);
         }
     }
 }
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Clone)]
 pub struct SearchInfo {
     pub start_time: Option<Instant>,
     pub seldepth: i8, // Maximum selective depth reached
@@ -127,10 +195,16 @@ pub struct SearchInfo {
     pub nodes: usize,
     pub ply: i8,
     pub killer_moves: KillerMoves,
+    pub heuristics: Heuristics,
     pub last_stats_sent: u128,     // When last stats update was sent
     pub last_curr_move_sent: u128, // When last current move was sent
     pub allocated_time: u128,      // Allotted msecs to spend on move
+    pub ponder_hit_time: u128,     // When "ponderhit" was received (msecs)
     pub terminated: SearchTerminate,
+    pub excluded_moves: Vec<ShortMove>, // Root moves already searched for MultiPV
+    pub root_lines: Vec<RootLine>,      // Best lines of the last iteration
+    pub verifying_null_move: bool,      // No null moves while verifying a cutoff
+    pub root_book_move: bool,           // The root move was taken from the book
 }
 
 impl SearchInfo {
@@ -142,10 +216,16 @@ impl SearchInfo {
             nodes: 0,
             ply: 0,
             killer_moves: [[ShortMove::new(0); MAX_KILLER_MOVES]; MAX_PLY as usize],
+            heuristics: Heuristics::new(),
             last_stats_sent: 0,
             last_curr_move_sent: 0,
             allocated_time: 0,
+            ponder_hit_time: 0,
             terminated: SearchTerminate::Nothing,
+            excluded_moves: Vec::new(),
+            root_lines: Vec::new(),
+            verifying_null_move: false,
+            root_book_move: false,
         }
     }
 
@@ -177,7 +257,7 @@ pub struct SearchCurrentMove {
 
 // This struct holds search statistics. These will be sent through the
 // engine thread to Comm, to be transmitted to the (G)UI.
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Copy, Clone, Debug, Serialize)]
 pub struct SearchStats {
     pub time: u128,     // Time spent searching
     pub nodes: usize,   // Number of nodes searched
@@ -185,7 +265,7 @@ pub struct SearchStats {
     pub hash_full: u16, // TT full in permille
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize)]
 pub struct PerftSummary {
     pub depth: i8,  // depth reached during search
     pub nodes: i32, // depth reached during search
@@ -195,29 +275,59 @@ pub struct PerftSummary {
 }
 
 #[derive(Clone, PartialEq, Debug)]
+pub struct BenchSummary {
+    pub depth: i8,         // Depth each position was searched to
+    pub nodes: Vec<usize>, // Nodes searched per position
+    pub time: u128,        // milliseconds, for all positions
+    pub interrupted: bool, // Stopped before all positions were searched
+}
+
+// Tells if the score of a summary is exact, or only a bound because the
+// aspiration window failed. A lower bound means the score is at least
+// this high (fail-high); an upper bound means it is at most this high.
+#[derive(PartialEq, Copy, Clone, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize)]
 pub struct SearchSummary {
-    pub depth: i8,    // depth reached during search
-    pub seldepth: i8, // Maximum selective depth reached
-    pub time: u128,   // milliseconds
-    pub cp: i16,      // centipawns score
-    pub mate: u8,     // mate in X moves
-    pub nodes: usize, // nodes searched
-    pub nps: usize,   // nodes per second
-    // pub hash_full: u16, // TT use in permille
+    pub depth: i8,        // depth reached during search
+    pub seldepth: i8,     // Maximum selective depth reached
+    pub time: u128,       // milliseconds
+    pub cp: i16,          // centipawns score
+    pub mate: i16,        // mate in X moves (negative if getting mated)
+    pub bound: Bound,     // Exact score, or a bound of a failed window
+    pub multipv: u8,      // Rank of this line in MultiPV mode
+    pub wdl: Option<Wdl>, // Win/draw/loss chances, if requested
+    pub nodes: usize,     // nodes searched
+    pub nps: usize,       // nodes per second
+    pub hash_full: u16,   // TT use in permille
+    #[serde(skip)] // Notation depends on the board; see pv_as_string().
     pub pv: Vec<Move>, // Principal Variation
 }
 
 impl SearchSummary {
-    pub fn pv_as_string(&self) -> String {
+    pub fn pv_as_string(&self, chess960: bool) -> String {
         let mut pv = String::from("");
         for next_move in self.pv.iter() {
-            let m = format!(" {}", next_move.as_string());
+            let m = format!(" {}", next_move.as_notation(chess960));
             pv.push_str(&m[..]);
         }
         pv.trim().to_string()
     }
 }
 
+// One of the best lines found at the root in MultiPV mode.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RootLine {
+    pub score: i16,    // Score of the line
+    pub pv: Vec<Move>, // Principal Variation, starting with the root move
+}
+
 pub struct SearchRefs<'a> {
     pub board: &'a mut Board,
     pub move_generator: &'a MoveGenerator,
@@ -233,8 +343,9 @@ pub struct SearchRefs<'a> {
 // This struct holds all the reports a search can send to the engine.
 #[derive(PartialEq, Debug)]
 pub enum SearchReport {
-    Finished(Move), // Search done. Contains the best move.
+    Finished(Move, Option<Move>), // Search done. Contains best and ponder move.
     PerftScore(PerftSummary),
+    BenchScore(BenchSummary),     // Nodes searched for the bench positions.
     SearchSummary(SearchSummary), // Periodic intermediate results.
     SearchCurrentMove(SearchCurrentMove), // Move currently searched.
     SearchStats(SearchStats),     // General search statistics
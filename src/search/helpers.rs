@@ -1,13 +1,16 @@
 use crate::{
     board::{defs::Pieces, Board},
-    defs::{Sides, MAX_MOVE_RULE},
+    defs::{EngineError, Sides, MAX_MOVE_RULE, MAX_PLY},
 };
 
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-use super::{defs::SearchRefs, Search};
+use super::{
+    defs::{SearchRefs, CHECKMATE, CHECKMATE_THRESHOLD},
+    Search,
+};
 
 pub type MoveBook = HashMap<String, Vec<(String, u32)>>;
 
@@ -21,19 +24,19 @@ impl Search {
     pub fn is_repition(board: &Board) -> bool {
         let mut count = 0;
         let mut stop = false;
-        let len = board.history.len();
-
-        // Ensure there is history to check
-        if len < 1 {
-            return false;
-        }
-        let mut x = board.history.len() - 1;
+        let mut x = board.history.len();
 
         // Traverse the history in reverse
         while x > 0 && !stop {
-            x -= 1; // Decrement first since we are indexing from len() - 1
+            x -= 1;
             let historic = board.history.get_ref(x);
 
+            // Positions before a null move don't count: the side that
+            // passed didn't really play to get back to them.
+            if historic.next_move.get_move() == 0 {
+                return false;
+            }
+
             // If zobrist keys are the same, we found a repetition
             if historic.zobrist_key == board.gamestate.zobrist_key {
                 count += 1;
@@ -51,6 +54,31 @@ impl Search {
         false
     }
 
+    // Convert a score into the number of moves until mate. This is
+    // negative if the side to move is getting mated, and 0 if the score
+    // is not a mate score.
+    pub fn mate_in(score: i16) -> i16 {
+        if score.abs() >= CHECKMATE_THRESHOLD && score.abs() <= CHECKMATE {
+            // Number of plies to mate, rounded up to full moves.
+            let plies = CHECKMATE - score.abs();
+            let moves = (plies + 1) / 2;
+            if score > 0 {
+                moves
+            } else {
+                -moves
+            }
+        } else {
+            0
+        }
+    }
+
+    // The depth at which a mate in the requested number of moves must
+    // have been found: the engine's moves and the opponent's replies.
+    pub fn mate_depth(refs: &SearchRefs) -> i8 {
+        let moves = refs.search_params.mate.max(1) as i16;
+        (moves * 2 - 1).min(MAX_PLY as i16) as i8
+    }
+
     // This function calculates the number of nodes per second.
     pub fn nodes_per_second(nodes: usize, msecs: u128) -> usize {
         let mut nps: usize = 0;
@@ -64,28 +92,30 @@ impl Search {
         nps
     }
 
-    pub fn load_book(filename: &str) -> MoveBook {
+    pub fn load_book(filename: &str) -> Result<MoveBook, EngineError> {
         let mut book = MoveBook::new();
-        let file = File::open(filename).expect("Failed to open book file");
+        let file = File::open(filename).map_err(|e| EngineError::io(filename, &e))?;
         let reader = BufReader::new(file);
         let mut current_pos = String::new();
 
-        for line in reader.lines() {
-            let line = line.expect("Failed to read line");
+        for (nr, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| EngineError::io(filename, &e))?;
             if line.starts_with("pos ") {
                 current_pos = line[4..].to_string();
             } else {
                 let parts: Vec<&str> = line.split_whitespace().collect();
                 if parts.len() == 2 {
                     let mv = parts[0].to_string();
-                    let weight: u32 = parts[1].parse().expect("Invalid weight");
+                    let weight: u32 = parts[1]
+                        .parse()
+                        .map_err(|_| EngineError::Book(filename.to_string(), nr + 1))?;
                     book.entry(current_pos.clone())
                         .or_insert_with(Vec::new)
                         .push((mv, weight));
                 }
             }
         }
-        book
+        Ok(book)
     }
 }
 
@@ -0,0 +1,139 @@
+use super::defs::{CONTINUATION_PLIES, HISTORY_BONUS_MAX, HISTORY_MAX};
+use crate::{
+    board::Board,
+    defs::{NrOf, Side, Sides},
+    movegen::defs::{Move, MoveList, ShortMove},
+};
+
+// An earlier move is remembered by the side that played it, the piece that
+// moved, and the square it moved to. This gives the number of such moves.
+const PIECE_TO: usize = Sides::BOTH * NrOf::PIECE_TYPES * NrOf::SQUARES;
+
+// The moves that led to the current position: the opponent's last move,
+// and our own move before that. None if there was no such move, or if it
+// was a null move.
+pub type MoveContext = [Option<usize>; CONTINUATION_PLIES];
+
+// The heuristics used to order quiet moves, learned during a search:
+//
+// - History: how often a move from one square to another caused a cutoff,
+//   for each side, regardless of the position. (Butterfly table.)
+// - Countermoves: the quiet move that last refuted the opponent's move.
+// - Continuation history: the same as the history, but for a move played
+//   after a specific earlier move: one ply back, and two plies back.
+//
+// Good moves get a bonus, moves that were searched before a cutoff get
+// a malus. The tables are large, so they are kept on the heap.
+#[derive(PartialEq, Clone)]
+pub struct Heuristics {
+    history: Vec<i16>,            // [side][from][to]
+    countermoves: Vec<ShortMove>, // [previous move]
+    continuation: Vec<Vec<i16>>,  // [plies back][previous move][piece][to]
+}
+
+impl Heuristics {
+    pub fn new() -> Self {
+        let butterfly = Sides::BOTH * NrOf::SQUARES * NrOf::SQUARES;
+        let continuation = PIECE_TO * NrOf::PIECE_TYPES * NrOf::SQUARES;
+
+        Self {
+            history: vec![0; butterfly],
+            countermoves: vec![ShortMove::new(0); PIECE_TO],
+            continuation: vec![vec![0; continuation]; CONTINUATION_PLIES],
+        }
+    }
+
+    // Find the moves that led to the current position. The last move in
+    // the history was played by the opponent, the one before it by us.
+    pub fn context(board: &Board) -> MoveContext {
+        let mut context = [None; CONTINUATION_PLIES];
+        let played = board.history.len();
+
+        for (plies_back, previous) in context.iter_mut().enumerate().take(played) {
+            let m = board.history.get_ref(played - 1 - plies_back).next_move;
+            let side = board.side_to_move() ^ ((plies_back + 1) & 1);
+
+            if m.get_move() != 0 {
+                *previous = Some(Heuristics::piece_to(side, m));
+            }
+        }
+
+        context
+    }
+
+    // Score a quiet move. The higher the score, the better the move did in
+    // the search up to now.
+    pub fn score(&self, side: Side, m: Move, context: &MoveContext) -> i32 {
+        let mut score = self.history[Heuristics::butterfly(side, m)] as i32;
+
+        for (table, previous) in self.continuation.iter().zip(context) {
+            if let Some(p) = previous {
+                score += table[Heuristics::continuation(*p, m)] as i32;
+            }
+        }
+
+        score
+    }
+
+    // The move that refuted the opponent's last move before.
+    pub fn countermove(&self, context: &MoveContext) -> ShortMove {
+        context[0].map_or(ShortMove::new(0), |p| self.countermoves[p])
+    }
+
+    // The quiet move "best" caused a beta cutoff. It gets a bonus, and the
+    // quiet moves that were searched before it get a malus, because they
+    // didn't. The deeper the search, the larger the update.
+    pub fn update(
+        &mut self,
+        side: Side,
+        best: Move,
+        tried: &MoveList,
+        depth: i8,
+        context: &MoveContext,
+    ) {
+        let depth = depth.max(1) as i32;
+        let bonus = (16 * depth * depth).min(HISTORY_BONUS_MAX);
+
+        for i in 0..tried.len() {
+            self.update_move(side, tried.get_move(i), -bonus, context);
+        }
+        self.update_move(side, best, bonus, context);
+
+        if let Some(p) = context[0] {
+            self.countermoves[p] = best.to_short_move();
+        }
+    }
+
+    fn update_move(&mut self, side: Side, m: Move, bonus: i32, context: &MoveContext) {
+        Heuristics::gravity(&mut self.history[Heuristics::butterfly(side, m)], bonus);
+
+        for (table, previous) in self.continuation.iter_mut().zip(context) {
+            if let Some(p) = previous {
+                Heuristics::gravity(&mut table[Heuristics::continuation(*p, m)], bonus);
+            }
+        }
+    }
+
+    // Add the bonus to the entry, but less of it as the entry gets closer
+    // to HISTORY_MAX in the same direction. Entries can't grow past the
+    // maximum this way, and moves that stop causing cutoffs lose their
+    // high score quickly.
+    fn gravity(entry: &mut i16, bonus: i32) {
+        let value = *entry as i32;
+        *entry = (value + bonus - value * bonus.abs() / HISTORY_MAX) as i16;
+    }
+
+    fn butterfly(side: Side, m: Move) -> usize {
+        (side * NrOf::SQUARES + m.from()) * NrOf::SQUARES + m.to()
+    }
+
+    fn piece_to(side: Side, m: Move) -> usize {
+        (side * NrOf::PIECE_TYPES + m.piece()) * NrOf::SQUARES + m.to()
+    }
+
+    // The side of the move is implied by the previous move: it is the
+    // opponent one ply back, and the same side two plies back.
+    fn continuation(previous: usize, m: Move) -> usize {
+        (previous * NrOf::PIECE_TYPES + m.piece()) * NrOf::SQUARES + m.to()
+    }
+}
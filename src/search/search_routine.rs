@@ -1,19 +1,39 @@
 use super::{
-    defs::{SearchRefs, SearchReport, SearchResult, SearchSummary, INF},
+    defs::{
+        Bound, RootLine, SearchMode, SearchRefs, SearchReport, SearchResult, SearchSummary,
+        ASPIRATION_MAX_SCORE, ASPIRATION_MIN_DEPTH, ASPIRATION_WINDOW, INF,
+    },
+    skill::Skill,
     Search,
 };
-use crate::{defs::MAX_PLY, engine::defs::Information, movegen::defs::Move};
+use crate::{defs::MAX_PLY, engine::defs::Information, evaluation::wdl::wdl, movegen::defs::Move};
+use std::cmp::Reverse;
 
 // Actual search routine
 impl Search {
     pub fn search_routine(refs: &mut SearchRefs) -> SearchResult {
         let mut depth = 1;
         let mut best_move = Move::new(0);
+        let mut ponder_move: Option<Move> = None;
         let mut possible_moves: Vec<Move> = Vec::new();
         let mut stop = false;
+        let mut mate_done = false;
 
-        let alpha: i16 = -INF;
-        let beta: i16 = INF;
+        // At a lower skill level, the search is cut short and searches
+        // more lines, to pick a weaker move from.
+        let skill = Skill::new(refs.search_params.skill_level);
+        let limited = skill.limit(refs.search_params);
+
+        // Number of lines to search. In MultiPV mode, the root is searched
+        // once per line, excluding the root moves of the lines found before.
+        // At a lower skill level, more lines may be searched than reported.
+        let multi_pv = refs.search_params.multi_pv.max(1) as usize;
+        let search_lines = multi_pv.max(refs.search_params.skill_lines as usize);
+
+        // When playing with a clock, determine how much of it we may use.
+        if refs.search_params.has_mode(SearchMode::GameTime) {
+            refs.search_info.allocated_time = Search::calculate_time_slice(refs);
+        }
 
         refs.search_info.start_timer();
 
@@ -21,45 +41,214 @@ impl Search {
             // set current depth
             refs.search_info.depth = depth;
 
-            // get eval for position
-            let eval = Search::alpha_beta(depth, alpha, beta, &mut possible_moves, refs);
+            // The best lines found in this iteration, best line first.
+            let mut lines: Vec<RootLine> = Vec::new();
+            refs.search_info.excluded_moves.clear();
 
-            // if not interupted
+            while lines.len() < search_lines {
+                // The same line of the previous iteration, to aim the
+                // aspiration window at.
+                let previous = refs.search_info.root_lines.get(lines.len()).cloned();
+                let multipv = (lines.len() + 1) as u8;
+
+                // get eval for position
+                let eval = Search::aspiration_search(
+                    depth,
+                    previous.as_ref(),
+                    multipv,
+                    &mut possible_moves,
+                    refs,
+                );
+
+                // An unfinished line can't be used.
+                if refs.search_info.interupted() {
+                    break;
+                }
+
+                // Nothing left to search: all root moves are in a line.
+                if possible_moves.is_empty() && !lines.is_empty() {
+                    break;
+                }
+
+                lines.push(RootLine {
+                    score: eval,
+                    pv: possible_moves.clone(),
+                });
+
+                // Without moves, there is no next line to search. A move
+                // from the book is played as it is; there is nothing to
+                // choose from.
+                if possible_moves.is_empty() || refs.search_info.root_book_move {
+                    break;
+                }
+
+                let root_move = possible_moves[0].to_short_move();
+                refs.search_info.excluded_moves.push(root_move);
+            }
+
+            // Order the lines from best to worst if all of them are done.
+            // Otherwise only the first line is useful: it is a search of all
+            // the root moves, so its best move can still be used.
             if !refs.search_info.interupted() {
+                lines.sort_by_key(|l| Reverse(l.score));
+            }
+
+            if let Some(line) = lines.first() {
                 // save best move
-                if !possible_moves.is_empty() {
-                    best_move = possible_moves[0];
+                if !line.pv.is_empty() {
+                    best_move = line.pv[0];
+                    // The move we expect the opponent to play in reply.
+                    ponder_move = line.pv.get(1).copied();
                 }
+            }
 
-                // Create summary of search
-                let elapsed = refs.search_info.time_elapsed();
-                let nodes = refs.search_info.nodes;
-                let summary = SearchSummary {
-                    depth,
-                    seldepth: refs.search_info.seldepth,
-                    time: elapsed,
-                    cp: eval,
-                    mate: 0,
-                    nodes,
-                    nps: Search::nodes_per_second(nodes, elapsed),
-                    pv: possible_moves.clone(),
-                };
+            // if not interupted
+            if !refs.search_info.interupted() {
+                // Create summary of search for each of the lines
+                for (i, line) in lines.iter().enumerate() {
+                    let multipv = (i + 1) as u8;
+                    Search::send_summary(depth, line, multipv, Bound::Exact, refs);
+                }
 
-                // println!("{:?}", &summary);
-                let report = SearchReport::SearchSummary(summary);
-                let information = Information::Search(report);
-                refs.report_tx
-                    .send(information)
-                    .expect("Couldnt send info to info channel");
+                // In mate search mode, the search is done when the mate is
+                // found, or when the depth needed to find it was searched.
+                if refs.search_params.has_mode(SearchMode::Mate) {
+                    let mate = lines.first().map_or(0, |l| Search::mate_in(l.score));
+                    let found = mate > 0 && mate <= refs.search_params.mate as i16;
+                    mate_done = found || depth >= Search::mate_depth(refs);
+                }
 
+                // Keep the lines, so the next iteration searches them first.
+                refs.search_info.root_lines = lines;
                 depth += 1;
             }
 
             // Stop deepening the search if the current depth was
             // interrupted, or if the time is up.
-            stop = refs.search_info.interupted();
+            stop = refs.search_info.interupted() || mate_done;
+        }
+        refs.search_info.excluded_moves.clear();
+
+        // Play a move of the lines found in the last complete iteration.
+        if limited {
+            if let Some(line) = skill.pick(&refs.search_info.root_lines) {
+                best_move = line.pv.first().copied().unwrap_or(best_move);
+                ponder_move = line.pv.get(1).copied();
+            }
         }
+
+        // Don't report the best move while still pondering.
+        Search::wait_for_ponder_end(refs);
+
         // refs.board.make_move(best_move, refs.move_generator);
-        (best_move, refs.search_info.terminated)
+        (best_move, ponder_move, refs.search_info.terminated)
+    }
+
+    // Search the root in a small window around the score of the previous
+    // iteration. This cuts off more, but when the score falls outside of
+    // the window, it is only a bound. The window is then widened on the
+    // side it failed, twice as far each time, and the root is searched
+    // again. Scores far outside of the normal range, such as mate scores,
+    // don't fit in a window; there the search is done without one.
+    fn aspiration_search(
+        depth: i8,
+        previous: Option<&RootLine>,
+        multipv: u8,
+        pv: &mut Vec<Move>,
+        refs: &mut SearchRefs,
+    ) -> i16 {
+        let mut delta = ASPIRATION_WINDOW;
+        let aim = previous.filter(|line| {
+            depth >= ASPIRATION_MIN_DEPTH && line.score.abs() < ASPIRATION_MAX_SCORE
+        });
+        let (mut alpha, mut beta) = match aim {
+            Some(line) => (line.score - delta, line.score + delta),
+            None => (-INF, INF),
+        };
+
+        loop {
+            pv.clear();
+            let eval = Search::alpha_beta(depth, alpha, beta, pv, refs);
+
+            if refs.search_info.interupted() {
+                return eval;
+            }
+
+            let bound = if eval <= alpha && alpha > -INF {
+                Bound::Upper
+            } else if eval >= beta && beta < INF {
+                Bound::Lower
+            } else {
+                return eval;
+            };
+
+            // A fail-low has no PV, because no root move raised alpha.
+            // Report the line of the previous iteration instead.
+            let line = RootLine {
+                score: eval,
+                pv: match (bound, previous) {
+                    (Bound::Upper, Some(line)) => line.pv.clone(),
+                    _ => pv.clone(),
+                },
+            };
+            Search::send_summary(depth, &line, multipv, bound, refs);
+
+            // Widen the window on the side it failed. On a fail-low, beta is
+            // lowered as well: the score is now known to be below alpha.
+            delta = delta.saturating_mul(2);
+            if bound == Bound::Upper {
+                beta = (alpha + beta) / 2;
+                alpha = eval.saturating_sub(delta).max(-INF);
+            } else {
+                beta = eval.saturating_add(delta).min(INF);
+            }
+
+            // Once the score is out of the normal range, it is probably a
+            // mate score. Open the window up fully on that side, instead of
+            // widening it step by step towards the mate.
+            if alpha <= -ASPIRATION_MAX_SCORE {
+                alpha = -INF;
+            }
+            if beta >= ASPIRATION_MAX_SCORE {
+                beta = INF;
+            }
+        }
+    }
+
+    // Send a summary of a searched root line to the engine. Lines past the
+    // ones the user asked for are only searched to pick a weaker move from,
+    // and are not reported.
+    fn send_summary(depth: i8, line: &RootLine, multipv: u8, bound: Bound, refs: &mut SearchRefs) {
+        if multipv > refs.search_params.multi_pv.max(1) {
+            return;
+        }
+
+        let elapsed = refs.search_info.time_elapsed();
+        let nodes = refs.search_info.nodes;
+        let material = refs.board.gamestate.material;
+        let hash_full = refs.tt.lock().expect("Error locking TT").hash_full();
+        let summary = SearchSummary {
+            depth,
+            seldepth: refs.search_info.seldepth,
+            time: elapsed,
+            cp: line.score,
+            mate: Search::mate_in(line.score),
+            bound,
+            multipv,
+            wdl: refs
+                .search_params
+                .show_wdl
+                .then(|| wdl(line.score, material)),
+            nodes,
+            nps: Search::nodes_per_second(nodes, elapsed),
+            hash_full,
+            pv: line.pv.clone(),
+        };
+
+        let report = SearchReport::SearchSummary(summary);
+        let information = Information::Search(report);
+        refs.report_tx
+            .send(information)
+            .expect("Couldnt send info to info channel");
     }
 }
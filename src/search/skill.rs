@@ -0,0 +1,96 @@
+use rand::{thread_rng, Rng};
+
+use super::defs::{RootLine, SearchMode, SearchParams};
+use crate::evaluation::defs::CENTIPAWNS_PER_UNIT;
+
+// Skill levels run from 0 (weakest) to 20 (full strength).
+pub const SKILL_LEVEL_MIN: u8 = 0;
+pub const SKILL_LEVEL_MAX: u8 = 20;
+
+// Elo range that UCI_Elo maps onto the skill levels. The top of the range
+// is full strength.
+pub const ELO_MIN: u16 = 800;
+pub const ELO_MAX: u16 = 2200;
+
+const SKILL_LINES: u8 = 4; // Root lines to choose the move from.
+const MARGIN_PER_LEVEL: i32 = 5; // Centipawns of error allowed per level.
+
+// A weaker engine is made by limiting how deep and how long it may search,
+// and by letting it play a move that is not the best, now and then. The
+// lower the level, the more the engine errs, and the bigger its errors.
+pub struct Skill {
+    level: u8,
+}
+
+impl Skill {
+    pub fn new(level: u8) -> Self {
+        Self {
+            level: level.min(SKILL_LEVEL_MAX),
+        }
+    }
+
+    // The skill level that plays at about the given rating.
+    pub fn from_elo(elo: u16) -> Self {
+        let elo = elo.clamp(ELO_MIN, ELO_MAX) - ELO_MIN;
+        let range = ELO_MAX - ELO_MIN;
+        let level = elo * SKILL_LEVEL_MAX as u16 / range;
+        Self::new(level as u8)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.level < SKILL_LEVEL_MAX
+    }
+
+    // Cap the depth and number of nodes, and search enough lines to pick
+    // a move from. An infinite search is analysis, not play, and is left
+    // alone. Returns true if the search was limited.
+    pub fn limit(&self, sp: &mut SearchParams) -> bool {
+        if !self.enabled() || sp.has_mode(SearchMode::Infinite) {
+            return false;
+        }
+
+        let level = self.level as usize;
+        let depth = 1 + self.level as i8 / 2;
+        let nodes = (level + 1) * (level + 1) * 1_000;
+
+        sp.depth = sp.depth.min(depth);
+        sp.add_mode(SearchMode::Depth);
+
+        sp.nodes = if sp.has_mode(SearchMode::Nodes) {
+            sp.nodes.min(nodes)
+        } else {
+            nodes
+        };
+        sp.add_mode(SearchMode::Nodes);
+
+        sp.skill_lines = SKILL_LINES;
+        true
+    }
+
+    // Choose the move to play from the best lines of the search, best
+    // line first. Each line gets a random bonus of up to a margin that
+    // grows as the level goes down, and the line that ends up on top is
+    // played. At level 0, moves up to about a pawn worse than the best
+    // move stand a fair chance; at level 19, only near equal moves do.
+    // Scores are in pawns, so they are compared in centipawns.
+    pub fn pick<'a>(&self, lines: &'a [RootLine]) -> Option<&'a RootLine> {
+        let margin = (SKILL_LEVEL_MAX - self.level) as i32 * MARGIN_PER_LEVEL;
+        let mut rng = thread_rng();
+        let mut best: Option<(&RootLine, i32)> = None;
+
+        // Keep the better line if two of them tie.
+        for line in lines {
+            let cp = line.score as i32 * CENTIPAWNS_PER_UNIT;
+            let score = cp + rng.gen_range(0..=margin);
+            if best.is_none_or(|(_, s)| score > s) {
+                best = Some((line, score));
+            }
+        }
+
+        best.map(|(line, _)| line)
+    }
+}
@@ -1,7 +1,4 @@
-use super::{
-    defs::{SearchRefs, MAX_KILLER_MOVES},
-    Search,
-};
+use super::{defs::SearchRefs, heuristics::Heuristics, Search};
 use crate::{
     board::defs::Pieces,
     defs::NrOf,
@@ -11,6 +8,9 @@ use crate::{
 const MVV_LVA_OFFSET: u32 = u32::MAX - 256;
 const TTMOVE_SORT_VALUE: u32 = 60;
 const KILLER_VALUE: u32 = 10;
+const COUNTERMOVE_VALUE: u32 = 30;
+const HISTORY_SORT_OFFSET: i32 = 65_536; // Keeps history scores above 0
+const ROOT_LINE_SORT_VALUE: u32 = 200;
 
 // MVV_VLA[victim][attacker]
 pub const MVV_LVA: [[u16; NrOf::PIECE_TYPES + 1]; NrOf::PIECE_TYPES + 1] = [
@@ -25,26 +25,47 @@ pub const MVV_LVA: [[u16; NrOf::PIECE_TYPES + 1]; NrOf::PIECE_TYPES + 1] = [
 
 impl Search {
     pub fn score_moves(move_list: &mut MoveList, short_move: ShortMove, refs: &SearchRefs) {
+        let side = refs.board.side_to_move();
+        let heuristics = &refs.search_info.heuristics;
+        let context = Heuristics::context(refs.board);
+        let countermove = heuristics.countermove(&context);
+        let killers = refs.search_info.killer_moves[refs.search_info.ply as usize];
+
         for x in 0..move_list.len() {
             let t_move = move_list.get_mut_move(x);
-            let mut value: u32 = 0;
+            // At the root, the lines of the previous iteration go first, in
+            // the order of their scores.
+            let root_line = if refs.search_info.ply == 0 {
+                refs.search_info
+                    .root_lines
+                    .iter()
+                    .position(|l| l.pv.first().map(|m| m.get_move()) == Some(t_move.get_move()))
+            } else {
+                None
+            };
+
             // Sort moves // TT first, Then Capture, then quiet moves
-            if t_move.get_move() == short_move.get_move() {
-                value = MVV_LVA_OFFSET + TTMOVE_SORT_VALUE;
+            let value = if let Some(i) = root_line {
+                MVV_LVA_OFFSET + ROOT_LINE_SORT_VALUE - i as u32
+            } else if t_move.get_move() == short_move.get_move() {
+                MVV_LVA_OFFSET + TTMOVE_SORT_VALUE
             } else if t_move.captured() != Pieces::NONE {
                 // Set capture higher then MVV_LVA offset
-                value = MVV_LVA_OFFSET + MVV_LVA[t_move.captured()][t_move.piece()] as u32;
+                MVV_LVA_OFFSET + MVV_LVA[t_move.captured()][t_move.piece()] as u32
+            } else if let Some(n) = killers
+                .iter()
+                .position(|k| k.get_move() == t_move.get_move())
+            {
+                // Then the killers, the first one before the second...
+                MVV_LVA_OFFSET - KILLER_VALUE * (n as u32 + 1)
+            } else if t_move.get_move() == countermove.get_move() {
+                // ...and the move that refuted the opponent's move before.
+                MVV_LVA_OFFSET - COUNTERMOVE_VALUE
             } else {
-                let ply = refs.search_info.ply as usize;
-                let mut n = 0;
-                while n < MAX_KILLER_MOVES && value == 0 {
-                    let killer = refs.search_info.killer_moves[ply][n];
-                    if t_move.get_move() == killer.get_move() {
-                        value = MVV_LVA_OFFSET - ((x as u32 + 1) & KILLER_VALUE);
-                    }
-                    n += 1;
-                }
-            }
+                // Other quiet moves go last, by how well they did so far.
+                let score = heuristics.score(side, *t_move, &context);
+                (HISTORY_SORT_OFFSET + score) as u32
+            };
 
             t_move.set_sort_score(value);
         }
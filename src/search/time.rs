@@ -1,13 +1,24 @@
-use super::{defs::SearchRefs, Search};
-use crate::{movegen::defs::Move, search::defs::MAX_KILLER_MOVES};
+use super::{
+    defs::{SearchControl, SearchMode, SearchRefs, SearchTerminate},
+    heuristics::Heuristics,
+    Search,
+};
+use crate::{
+    defs::Sides,
+    movegen::defs::{Move, MoveList},
+    search::defs::MAX_KILLER_MOVES,
+};
 
+const GAME_LENGTH: usize = 25; // moves
+const MOVES_BUFFER: usize = 5; //  moves
 const CRITICAL_TIME: u128 = 1_000; // msecs
 const OK_TIME: u128 = CRITICAL_TIME * 5; // msecs
 
 impl Search {
-    // Determine if allocated search time has been used up.
+    // Determine if allocated search time has been used up. After a
+    // ponderhit, only the time since then counts.
     pub fn out_of_time(refs: &mut SearchRefs) -> bool {
-        let elapsed = refs.search_info.time_elapsed();
+        let elapsed = refs.search_info.time_elapsed() - refs.search_info.ponder_hit_time;
         let allocated = refs.search_info.allocated_time;
 
         // Calculate a factor with which it is allowed to overshoot the
@@ -22,6 +33,79 @@ impl Search {
 
         elapsed >= (overshoot_factor * allocated as f64).round() as u128
     }
+
+    // Calculates the time the engine allocates for searching a single
+    // move. This depends on the number of moves still to go in the game.
+    pub fn calculate_time_slice(refs: &SearchRefs) -> u128 {
+        // Calculate the time slice step by step.
+        let gt = &refs.search_params.game_time;
+        let mtg = Search::moves_to_go(refs) as f64;
+        let white = refs.board.side_to_move() == Sides::WHITE;
+        let clock = if white { gt.white_time } else { gt.black_time } as f64;
+        let increment = if white {
+            gt.white_time_incr
+        } else {
+            gt.black_time_incr
+        } as i128;
+        let overhead = refs.search_params.move_overhead as i128;
+        let base_time = (clock / mtg).round() as i128;
+        let time_slice = base_time + increment - overhead;
+
+        // Make sure we're never sending less than 0 msecs of available time.
+        if time_slice > 0 {
+            // Just send the calculated slice.
+            time_slice as u128
+        } else if (base_time + increment) > (overhead / 5) {
+            // Don't substract GUI lag protection (overhead) if this leads
+            // to a negative time allocation.
+            (base_time + increment) as u128
+        } else {
+            // We actually don't have any time.
+            0
+        }
+    }
+
+    // The opponent played the move we were pondering on. From now on the
+    // search is a normal timed search. The time spent pondering was free,
+    // so the time limits start counting from this moment.
+    pub fn ponder_hit(refs: &mut SearchRefs) {
+        let elapsed = refs.search_info.time_elapsed();
+        refs.search_params.ponder = false;
+        refs.search_params.move_time += elapsed;
+        if refs.search_params.has_mode(SearchMode::GameTime) {
+            refs.search_info.ponder_hit_time = elapsed;
+            refs.search_info.allocated_time = Search::calculate_time_slice(refs);
+        }
+    }
+
+    // A ponder search may not report its best move before the GUI sends
+    // "ponderhit" or "stop". If the search finishes early, wait for it.
+    pub fn wait_for_ponder_end(refs: &mut SearchRefs) {
+        while refs.search_params.ponder && !refs.search_info.interupted() {
+            match refs.control_rx.recv().unwrap_or(SearchControl::Quit) {
+                SearchControl::PonderHit => refs.search_params.ponder = false,
+                SearchControl::Stop => refs.search_info.terminated = SearchTerminate::Stop,
+                SearchControl::Quit => refs.search_info.terminated = SearchTerminate::Quit,
+                SearchControl::Start(..) | SearchControl::Nothing => (),
+            }
+        }
+    }
+
+    // Here we try to come up with some sort of sensible value for "moves
+    // to go", if this value is not supplied.
+    fn moves_to_go(refs: &SearchRefs) -> usize {
+        // If moves to go was supplied, then use this.
+        if let Some(x) = refs.search_params.game_time.moves_to_go {
+            x
+        } else {
+            // Guess moves to go if not supplied.
+            let white = refs.board.side_to_move() == Sides::WHITE;
+            let ply = refs.board.history.len();
+            let moves_played = if white { ply / 2 } else { (ply.max(1) - 1) / 2 };
+
+            GAME_LENGTH - (moves_played % GAME_LENGTH) + MOVES_BUFFER
+        }
+    }
 }
 
 // Killer moves and history heuristics.
@@ -50,4 +134,21 @@ impl Search {
             refs.search_info.killer_moves[ply][0] = current_move.to_short_move();
         }
     }
+
+    // A quiet move caused a beta cutoff. Raise its score in the history
+    // tables, and lower the scores of the quiet moves searched before it,
+    // which didn't cut off. It also becomes the countermove to the move
+    // the opponent just played.
+    pub fn update_history_heuristic(
+        current_move: Move,
+        quiets_tried: &MoveList,
+        depth: i8,
+        refs: &mut SearchRefs,
+    ) {
+        let side = refs.board.side_to_move();
+        let context = Heuristics::context(refs.board);
+        refs.search_info
+            .heuristics
+            .update(side, current_move, quiets_tried, depth, &context);
+    }
 }
@@ -1,7 +1,11 @@
 use super::{
-    defs::{SearchControl, SearchMode, SearchRefs, SearchTerminate},
+    defs::{
+        SearchControl, SearchCurrentMove, SearchMode, SearchRefs, SearchReport, SearchStats,
+        SearchTerminate, MIN_TIME_CURR_MOVE, MIN_TIME_STATS,
+    },
     Search,
 };
+use crate::{engine::defs::Information, movegen::defs::Move};
 
 impl Search {
     // Calc nodes searched per sec
@@ -21,40 +25,90 @@ impl Search {
     // This function checks termination conditions and sets the termination
     // flag if this is required.
     pub fn check_termination(refs: &mut SearchRefs) {
+        // Already terminated. Don't take any more commands from the
+        // channel; they are meant for after this search.
+        if refs.search_info.interupted() {
+            return;
+        }
+
         // Terminate search if stop or quit command is received.
         let cmd = refs.control_rx.try_recv().unwrap_or(SearchControl::Nothing);
         match cmd {
             SearchControl::Stop => refs.search_info.terminated = SearchTerminate::Stop,
             SearchControl::Quit => refs.search_info.terminated = SearchTerminate::Quit,
+            SearchControl::PonderHit => Search::ponder_hit(refs),
             SearchControl::Start(..) | SearchControl::Nothing => (),
         };
 
-        // Terminate search if certain conditions are met.
-        let search_mode = refs.search_params.search_mode;
-        match search_mode {
-            SearchMode::Depth => {
-                if refs.search_info.depth > refs.search_params.depth {
-                    refs.search_info.terminated = SearchTerminate::Stop
-                }
-            }
-            SearchMode::MoveTime => {
-                let elapsed = refs.search_info.time_elapsed();
-                if elapsed >= refs.search_params.move_time {
-                    refs.search_info.terminated = SearchTerminate::Stop
-                }
-            }
-            SearchMode::Nodes => {
-                if refs.search_info.nodes >= refs.search_params.nodes {
-                    refs.search_info.terminated = SearchTerminate::Stop
-                }
-            }
-            SearchMode::GameTime => {
-                if Search::out_of_time(refs) {
-                    refs.search_info.terminated = SearchTerminate::Stop
+        // While pondering, the clock is not running for us.
+        let pondering = refs.search_params.ponder;
+
+        // Terminate search if one of the limits is reached.
+        let mut i = 0;
+        while i < refs.search_params.search_modes.len() && !refs.search_info.interupted() {
+            let limit_reached = match refs.search_params.search_modes[i] {
+                SearchMode::Depth => refs.search_info.depth > refs.search_params.depth,
+                SearchMode::MoveTime => {
+                    !pondering && refs.search_info.time_elapsed() >= refs.search_params.move_time
                 }
+                SearchMode::Nodes => refs.search_info.nodes >= refs.search_params.nodes,
+                SearchMode::GameTime => !pondering && Search::out_of_time(refs),
+                SearchMode::Mate => false, // Checked after each iteration
+                SearchMode::Infinite => false, // Handled by a direct 'stop' command
+            };
+
+            if limit_reached {
+                refs.search_info.terminated = SearchTerminate::Stop;
             }
-            SearchMode::Infinite => (), // Handled by a direct 'stop' command
-            SearchMode::Nothing => (),  // We're not searching. Nothing to do.
+            i += 1;
+        }
+    }
+
+    // Send the search statistics to the engine, at most once every
+    // MIN_TIME_STATS, so long searches show progress. Not sent in quiet mode.
+    pub fn send_stats(refs: &mut SearchRefs) {
+        let elapsed = refs.search_info.time_elapsed();
+        let last_sent = refs.search_info.last_stats_sent;
+
+        if refs.search_params.quiet || elapsed < last_sent + MIN_TIME_STATS {
+            return;
+        }
+
+        let nodes = refs.search_info.nodes;
+        let stats = SearchStats {
+            time: elapsed,
+            nodes,
+            nps: Search::nodes_per_sec(nodes, elapsed),
+            hash_full: refs.tt.lock().expect("Error locking TT").hash_full(),
+        };
+
+        let information = Information::Search(SearchReport::SearchStats(stats));
+        refs.report_tx
+            .send(information)
+            .expect("Couldnt send info to info channel");
+        refs.search_info.last_stats_sent = elapsed;
+    }
+
+    // Send the root move that is being searched to the engine. This is only
+    // done after the search has been running for a while, and at most once
+    // every MIN_TIME_CURR_MOVE. Not sent in quiet mode.
+    pub fn send_current_move(m: Move, number: u8, refs: &mut SearchRefs) {
+        let elapsed = refs.search_info.time_elapsed();
+        let last_sent = refs.search_info.last_curr_move_sent;
+
+        if refs.search_params.quiet || elapsed < last_sent + MIN_TIME_CURR_MOVE {
+            return;
         }
+
+        let current = SearchCurrentMove {
+            curr_move: m,
+            curr_move_number: number,
+        };
+
+        let information = Information::Search(SearchReport::SearchCurrentMove(current));
+        refs.report_tx
+            .send(information)
+            .expect("Couldnt send info to info channel");
+        refs.search_info.last_curr_move_sent = elapsed;
     }
 }